@@ -51,7 +51,7 @@ fn get_answer(input: &str) -> i32 {
 
     left
         .iter()
-        .map(|v| v * right.get(&v).unwrap_or(&0))
+        .map(|v| v * right.get(v).unwrap_or(&0))
         .sum()
 }
 
@@ -1,56 +1,298 @@
 use utils::timer::Timer;
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fs::read;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-fn count_all_routes(mountain: &[u8], width: usize, current: usize) -> usize {
-    let current_value = mountain[current];
-    if current_value == b'9' {
-        return 1;
+// anything that isn't a digit is impassable
+struct TopoMap {
+    heights: Vec<Option<u8>>,
+    width: usize
+}
+
+impl From<&[u8]> for TopoMap {
+    // short rows are padded with impassable cells
+    fn from(data: &[u8]) -> Self {
+        let rows: Vec<&[u8]> = data
+            .split(|ch| *ch == b'\n')
+            .map(|row| row.strip_suffix(b"\r").unwrap_or(row))
+            .filter(|row| !row.is_empty())
+            .collect();
+
+        let width = rows
+            .iter()
+            .map(|row| row.len())
+            .max()
+            .unwrap_or(0);
+
+        let heights = rows
+            .iter()
+            .flat_map(|row| (0..width).map(|x| row
+                .get(x)
+                .filter(|ch| ch.is_ascii_digit())
+                .map(|ch| ch - b'0')))
+            .collect();
+
+        TopoMap {
+            heights,
+            width
+        }
     }
+}
 
-    let mut routes = 0;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepRule {
+    Ascend,
+    // up or down by at most this much
+    Within(u8),
+    Downhill
+}
 
-    if let Some(left) = current.checked_sub(1) {
-        if mountain[left] == current_value + 1 {
-            routes += count_all_routes(mountain, width, left);
+impl StepRule {
+    fn allows(self, from: u8, to: u8) -> bool {
+        match self {
+            StepRule::Ascend => to == from + 1,
+            StepRule::Within(k) => from.abs_diff(to) <= k,
+            StepRule::Downhill => to < from
         }
     }
 
-    if let Some(up) = current.checked_sub(width) {
-        if mountain[up] == current_value + 1 {
-            routes += count_all_routes(mountain, width, up);
+    // a Within trail can step back and forth forever, so only its scores
+    // are defined
+    fn has_ratings(self) -> bool {
+        !matches!(self, StepRule::Within(_))
+    }
+}
+
+#[derive(Debug)]
+struct StepRuleParseError;
+
+impl FromStr for StepRule {
+    type Err = StepRuleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("within", k)) => k
+                .parse()
+                .map(StepRule::Within)
+                .map_err(|_| StepRuleParseError),
+            None if s == "ascend" => Ok(StepRule::Ascend),
+            None if s == "downhill" => Ok(StepRule::Downhill),
+            _ => Err(StepRuleParseError)
         }
     }
+}
 
-    let right = current + 1;
-    if right < mountain.len() && mountain[right] == current_value + 1 {
-        routes += count_all_routes(mountain, width, right);
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    start: u8,
+    summit: u8,
+    step: StepRule
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            summit: 9,
+            step: StepRule::Ascend
+        }
+    }
+}
+
+impl TopoMap {
+    fn position(&self, idx: usize) -> (usize, usize) {
+        (idx % self.width, idx / self.width)
     }
 
-    let down = current + width;
-    if down < mountain.len() && mountain[down] == current_value + 1 {
-        routes += count_all_routes(mountain, width, down);
+    fn is_height(&self, idx: usize, height: u8) -> bool {
+        self.heights[idx] == Some(height)
     }
 
-    routes
+    // a trail ends at the first summit it reaches
+    fn next_steps(&self, current: usize, rules: &Rules) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = self.position(current);
+        let height = self.heights[current].filter(|h| *h != rules.summit);
+        let rows = self.heights.len() / self.width.max(1);
+        let step = rules.step;
+
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .filter_map(move |(dx, dy)| {
+                let nx = x.checked_add_signed(dx).filter(|nx| *nx < self.width)?;
+                let ny = y.checked_add_signed(dy).filter(|ny| *ny < rows)?;
+                let next = ny * self.width + nx;
+                let next_height = self.heights[next]?;
+                step.allows(height?, next_height).then_some(next)
+            })
+    }
+
+    fn trailheads<'a>(&'a self, rules: &'a Rules) -> impl Iterator<Item = usize> + 'a {
+        (0..self.heights.len()).filter(|idx| self.is_height(*idx, rules.start))
+    }
 }
 
-fn find_summits(mountain: &[u8], width: usize, current: usize, summits: &mut HashSet<usize>) {
-    let current_value = mountain[current];
-    if current_value == b'9' {
-        summits.insert(current);
-        return;
+struct Trails {
+    // None when the step rule doesn't give ratings
+    ratings: Option<Vec<usize>>,
+    scores: Vec<usize>
+}
+
+fn find_trails(map: &TopoMap, rules: &Rules) -> Trails {
+    let n = map.heights.len();
+
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (idx, steps) in successors.iter_mut().enumerate() {
+        for next in map.next_steps(idx, rules) {
+            steps.push(next);
+            predecessors[next].push(idx);
+        }
+    }
+
+    let summit_cells: Vec<usize> = (0..n)
+        .filter(|idx| map.is_height(*idx, rules.summit))
+        .collect();
+
+    // only cells which can reach a summit matter
+    let mut useful = vec![false; n];
+    let mut queue: VecDeque<usize> = summit_cells.iter().copied().collect();
+    summit_cells.iter().for_each(|idx| useful[*idx] = true);
+    while let Some(idx) = queue.pop_front() {
+        for &prev in &predecessors[idx] {
+            if !useful[prev] {
+                useful[prev] = true;
+                queue.push_back(prev);
+            }
+        }
+    }
+
+    // each cell is ready once all of the cells it can step to are done, so
+    // with the puzzle's rules this goes down one height at a time
+    let mut remaining: Vec<usize> = (0..n)
+        .map(|idx| successors[idx].iter().filter(|next| useful[**next]).count())
+        .collect();
+    let mut order = Vec::new();
+    let mut queue: VecDeque<usize> = summit_cells.iter().copied().collect();
+    while let Some(idx) = queue.pop_front() {
+        order.push(idx);
+        for &prev in &predecessors[idx] {
+            remaining[prev] -= 1;
+            if remaining[prev] == 0 {
+                queue.push_back(prev);
+            }
+        }
+    }
+
+    // the summits reachable from each cell, one bit per summit
+    let words = summit_cells.len().div_ceil(64);
+    let mut ratings: Vec<Option<usize>> = (0..n)
+        .map(|idx| if useful[idx] { None } else { Some(0) })
+        .collect();
+    let mut summits: Vec<Vec<u64>> = vec![vec![0; words]; n];
+
+    for (bit, &idx) in summit_cells.iter().enumerate() {
+        summits[idx][bit / 64] |= 1 << (bit % 64);
+    }
+
+    for &idx in &order {
+        if map.is_height(idx, rules.summit) {
+            ratings[idx] = Some(1);
+            continue;
+        }
+
+        let mut rating = 0;
+        let mut reachable = vec![0u64; words];
+        for &next in &successors[idx] {
+            rating += ratings[next].expect("Cells should be rated before the cells that reach them");
+            reachable
+                .iter_mut()
+                .zip(&summits[next])
+                .for_each(|(word, next)| *word |= next);
+        }
+
+        ratings[idx] = Some(rating);
+        summits[idx] = reachable;
     }
 
-    [-1, 1, width as isize, -(width as isize)]
+    // whatever is left can reach a loop, so repeat the union until nothing
+    // changes
+    let mut queue: VecDeque<usize> = (0..n)
+        .filter(|idx| useful[*idx] && ratings[*idx].is_none())
+        .collect();
+    let mut queued = vec![false; n];
+    queue.iter().for_each(|idx| queued[*idx] = true);
+    while let Some(idx) = queue.pop_front() {
+        queued[idx] = false;
+
+        let mut reachable = summits[idx].clone();
+        for &next in &successors[idx] {
+            reachable
+                .iter_mut()
+                .zip(&summits[next])
+                .for_each(|(word, next)| *word |= next);
+        }
+
+        if reachable != summits[idx] {
+            summits[idx] = reachable;
+            for &prev in &predecessors[idx] {
+                if ratings[prev].is_none() && !queued[prev] {
+                    queued[prev] = true;
+                    queue.push_back(prev);
+                }
+            }
+        }
+    }
+
+    let scores = summits
         .iter()
-        .filter_map(|next|
-            current
-                .checked_add_signed(*next)
-                .filter(|next| *next < mountain.len() && mountain[*next] == current_value + 1)
-        )
-        .for_each(|next| find_summits(mountain, width, next, summits));
+        .map(|bits| bits.iter().map(|word| word.count_ones() as usize).sum())
+        .collect();
+
+    let ratings = rules.step.has_ratings().then(|| ratings
+        .into_iter()
+        .map(|rating| rating.expect("Trails should not loop when they have ratings"))
+        .collect());
+
+    Trails {
+        ratings,
+        scores
+    }
+}
+
+fn list_trails(map: &TopoMap, rules: &Rules, start: usize, ratings: &[usize]) -> Vec<Vec<usize>> {
+    let mut trails = Vec::new();
+    let mut trail = vec![start];
+    let mut stack = vec![map.next_steps(start, rules).collect::<Vec<_>>()];
+
+    while let Some(options) = stack.last_mut() {
+        let current = *trail.last().expect("Trail should not be empty");
+        if map.is_height(current, rules.summit) {
+            trails.push(trail.clone());
+        }
+
+        match options.pop() {
+            Some(next) if ratings[next] != 0 => {
+                trail.push(next);
+                stack.push(map.next_steps(next, rules).collect());
+            },
+            // no summit this way
+            Some(_) => {},
+            None => {
+                stack.pop();
+                trail.pop();
+            }
+        }
+    }
+
+    trails
+}
+
+fn parse_height(arg: Option<String>, option: &str) -> u8 {
+    arg
+        .and_then(|h| h.parse().ok())
+        .filter(|h| *h <= 9)
+        .unwrap_or_else(|| panic!("Should have a height from 0 to 9 after {option}"))
 }
 
 fn main() {
@@ -61,37 +303,248 @@ fn main() {
         .expect("Should have file argument")
         .into();
 
-    let mountain = read(path)
+    let data = read(path)
         .expect("Should be able to read from path");
 
-    let width = mountain
-        .iter()
-        .position(|ch| *ch == b'\n')
-        .map(|w| w + 1)
-        .unwrap_or(mountain.len());
+    let map = TopoMap::from(data.as_slice());
 
-    let answer = if cfg!(feature = "part2") {
-        mountain
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, ch)| if *ch == b'0' {
-                Some(count_all_routes(&mountain, width, idx))
-            } else {
-                None
-            })
+    let mut rules = Rules::default();
+    let mut trailhead: Option<usize> = None;
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trails" => {
+                let position = args
+                    .next()
+                    .expect("Should have a trailhead after --trails");
+                let (x, y) = position
+                    .split_once(',')
+                    .expect("Trailhead should be written as X,Y");
+                let x: usize = x.parse().expect("X should be a number");
+                let y: usize = y.parse().expect("Y should be a number");
+                assert!(x < map.width, "Trailhead should be on the map");
+                trailhead = Some(y * map.width + x);
+            },
+            "--start" => rules.start = parse_height(args.next(), "--start"),
+            "--summit" => rules.summit = parse_height(args.next(), "--summit"),
+            "--step" => rules.step = args
+                .next()
+                .and_then(|s| s.parse().ok())
+                .expect("Step rule should be ascend, downhill or within=K"),
+            _ => panic!("Unknown option: {arg}")
+        }
+    }
+
+    let trails = find_trails(&map, &rules);
+
+    if let Some(start) = trailhead {
+        assert!(start < map.heights.len() && map.is_height(start, rules.start), "Trailhead should be at the start height");
+
+        let ratings = trails.ratings
+            .as_ref()
+            .expect("Step rule should be ascend or downhill to list trails");
+        for trail in list_trails(&map, &rules, start, ratings) {
+            let steps: Vec<String> = trail
+                .iter()
+                .map(|idx| format!("{:?}", map.position(*idx)))
+                .collect();
+            println!("{}", steps.join(" -> "));
+        }
+    }
+
+    let answer: usize = if cfg!(feature = "part2") {
+        let ratings = trails.ratings
+            .as_ref()
+            .expect("Step rule should be ascend or downhill to rate trails");
+        map
+            .trailheads(&rules)
+            .map(|idx| ratings[idx])
             .sum()
     }
     else {
-        mountain
-            .iter()
-            .enumerate()
-            .filter(|(_idx, ch)| **ch == b'0')
-            .fold(0usize, |acc, (idx, _ch)| {
-                let mut summits = HashSet::new();
-                find_summits(&mountain, width, idx, &mut summits);
-                acc + summits.len()
-            })
+        map
+            .trailheads(&rules)
+            .map(|idx| trails.scores[idx])
+            .sum()
     };
 
     println!("Answer: {answer}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &[u8] = b"\
+89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732
+";
+
+    fn totals_with(mountain: &[u8], rules: &Rules) -> (usize, Option<usize>) {
+        let map = TopoMap::from(mountain);
+        let trails = find_trails(&map, rules);
+        let score = map.trailheads(rules).map(|idx| trails.scores[idx]).sum();
+        let rating = trails.ratings.map(|ratings| map.trailheads(rules).map(|idx| ratings[idx]).sum());
+        (score, rating)
+    }
+
+    fn totals(mountain: &[u8]) -> (usize, Option<usize>) {
+        totals_with(mountain, &Rules::default())
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(totals(EXAMPLE), (36, Some(81)));
+    }
+
+    #[test]
+    fn test_published_scores() {
+        assert_eq!(totals(b"0123\n1234\n8765\n9876\n").0, 1);
+        assert_eq!(totals(b"...0...\n...1...\n...2...\n6543456\n7.....7\n8.....8\n9.....9\n").0, 2);
+        assert_eq!(totals(b"..90..9\n...1.98\n...2..7\n6543456\n765.987\n876....\n987....\n").0, 4);
+        assert_eq!(totals(b"10..9..\n2...8..\n3...7..\n4567654\n...8..3\n...9..2\n.....01\n").0, 3);
+    }
+
+    #[test]
+    fn test_published_ratings() {
+        assert_eq!(totals(b".....0.\n..4321.\n..5..2.\n..6543.\n..7..4.\n..8765.\n..9....\n").1, Some(3));
+        assert_eq!(totals(b"..90..9\n...1.98\n...2..7\n6543456\n765.987\n876....\n987....\n").1, Some(13));
+        assert_eq!(totals(b"012345\n123456\n234567\n345678\n4.6789\n56789.\n").1, Some(227));
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let crlf: Vec<u8> = EXAMPLE
+            .split(|ch| *ch == b'\n')
+            .flat_map(|row| row.iter().copied().chain(*b"\r\n"))
+            .collect();
+        assert_eq!(totals(&crlf), (36, Some(81)));
+        assert_eq!(totals(EXAMPLE.trim_ascii_end()), (36, Some(81)));
+    }
+
+    #[test]
+    fn test_impassable() {
+        // the newline used to be treated as terrain one higher than '\t'
+        assert_eq!(totals(b"0123456789\n").0, 1);
+        assert_eq!(totals(b"01234x6789\n"), (0, Some(0)));
+    }
+
+    #[test]
+    fn test_downhill() {
+        // downhill from 9 to 0 by any amount
+        let rules = Rules { start: 9, summit: 0, step: StepRule::Downhill };
+        assert_eq!(totals_with(b"9520\n", &rules), (1, Some(1)));
+        assert_eq!(totals_with(b"90\n50\n", &rules), (2, Some(2)));
+        assert_eq!(totals_with(b"9.0\n", &rules), (0, Some(0)));
+
+        // every puzzle trail reversed is a downhill trail, but not the other
+        // way around
+        let (score, rating) = totals_with(EXAMPLE, &rules);
+        assert!(score >= 36);
+        assert!(rating.unwrap() >= 81);
+    }
+
+    #[test]
+    fn test_within() {
+        // going back and forth makes infinitely many trails, so there are
+        // scores but no ratings
+        let rules = Rules { start: 0, summit: 9, step: StepRule::Within(1) };
+        assert_eq!(totals_with(b"0123456789\n", &rules), (1, None));
+
+        // not even where every trail happens to be finite
+        assert_eq!(totals_with(b"89\n", &Rules { start: 8, ..rules }), (1, None));
+
+        // the only way across is a jump of 3, and a trail ends at the first
+        // summit it reaches
+        let rules = Rules { start: 0, summit: 9, step: StepRule::Within(2) };
+        assert_eq!(totals_with(b"036999\n", &rules), (0, None));
+        let rules = Rules { start: 0, summit: 9, step: StepRule::Within(3) };
+        assert_eq!(totals_with(b"03699\n", &rules), (1, None));
+        assert_eq!(totals_with(b"9630369\n", &rules), (2, None));
+
+        // steps of zero are allowed too, so a plateau can be crossed
+        let rules = Rules { start: 0, summit: 9, step: StepRule::Within(0) };
+        assert_eq!(totals_with(b"0000\n9999\n", &rules), (0, None));
+        let rules = Rules { start: 5, summit: 9, step: StepRule::Within(4) };
+        assert_eq!(totals_with(b"9.55\n...9\n", &rules), (2, None));
+    }
+
+    #[test]
+    fn test_has_ratings() {
+        assert!(StepRule::Ascend.has_ratings());
+        assert!(StepRule::Downhill.has_ratings());
+        assert!(!StepRule::Within(0).has_ratings());
+        assert!(!StepRule::Within(9).has_ratings());
+    }
+
+    #[test]
+    fn test_parse_step_rule() {
+        assert_eq!("ascend".parse::<StepRule>().unwrap(), StepRule::Ascend);
+        assert_eq!("downhill".parse::<StepRule>().unwrap(), StepRule::Downhill);
+        assert_eq!("within=2".parse::<StepRule>().unwrap(), StepRule::Within(2));
+        assert!("within".parse::<StepRule>().is_err());
+    }
+
+    #[test]
+    fn test_list_trails() {
+        let map = TopoMap::from(EXAMPLE);
+        let rules = Rules::default();
+        let trails = find_trails(&map, &rules);
+
+        let ratings = trails.ratings.unwrap();
+
+        for start in map.trailheads(&rules) {
+            let listed = list_trails(&map, &rules, start, &ratings);
+            assert_eq!(listed.len(), ratings[start]);
+
+            for trail in &listed {
+                assert_eq!(trail.len(), 10);
+                for (height, idx) in trail.iter().enumerate() {
+                    assert!(map.is_height(*idx, height as u8));
+                }
+                for pair in trail.windows(2) {
+                    assert!(map.next_steps(pair[0], &rules).any(|next| next == pair[1]));
+                }
+            }
+
+            let mut unique = listed.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), listed.len());
+        }
+    }
+
+    #[test]
+    fn test_many_summits() {
+        // more summits than fit in a single word of the bitset
+        let mut mountain = Vec::new();
+        for digit in b'0'..=b'9' {
+            mountain.extend([digit; 100]);
+            mountain.push(b'\n');
+        }
+
+        assert_eq!(totals(&mountain), (100, Some(100)));
+    }
+
+    #[test]
+    fn test_diamond() {
+        // heights rise with the distance from a single trailhead, so every
+        // cell 9 steps away is a summit
+        let mountain: Vec<u8> = (-9i32..=9)
+            .flat_map(|y| (-9i32..=9)
+                .map(move |x| match x.abs() + y.abs() {
+                    d @ 0..=9 => b'0' + d as u8,
+                    _ => b'.'
+                })
+                .chain(std::iter::once(b'\n')))
+            .collect();
+
+        assert_eq!(totals(&mountain), (36, Some(2044)));
+    }
+}
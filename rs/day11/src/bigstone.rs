@@ -0,0 +1,137 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+// digits in the rules' radix, most significant first; the radix isn't
+// stored, so every operation has to be given the same one
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigStone(Vec<u8>);
+
+impl BigStone {
+    pub fn zero() -> Self {
+        Self(vec![0])
+    }
+
+    pub fn from_u64(mut value: u64, radix: u32) -> Self {
+        let mut digits = Vec::new();
+        loop {
+            digits.push((value % radix as u64) as u8);
+            value /= radix as u64;
+            if value == 0 {
+                break;
+            }
+        }
+
+        digits.reverse();
+        Self(digits)
+    }
+
+    pub fn from_decimal(s: &str, radix: u32) -> Option<Self> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(s
+            .bytes()
+            .fold(Self::zero(), |value, b| value.mul_add(10, (b - b'0') as u64, radix)))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0]
+    }
+
+    pub fn digits(&self) -> usize {
+        self.0.len()
+    }
+
+    fn from_digits(digits: &[u8]) -> Self {
+        let first = digits
+            .iter()
+            .position(|&d| d != 0)
+            .unwrap_or(digits.len() - 1);
+        Self(digits[first..].to_vec())
+    }
+
+    pub fn split(&self, low_digits: usize) -> [Self; 2] {
+        let (high, low) = self.0.split_at(self.0.len() - low_digits);
+        [Self::from_digits(high), Self::from_digits(low)]
+    }
+
+    pub fn mul_add(&self, factor: u64, addend: u64, radix: u32) -> Self {
+        let radix = radix as u128;
+        let mut digits = Vec::with_capacity(self.0.len() + 20);
+        let mut carry = addend as u128;
+        for &d in self.0.iter().rev() {
+            let product = d as u128 * factor as u128 + carry;
+            digits.push((product % radix) as u8);
+            carry = product / radix;
+        }
+        while carry > 0 {
+            digits.push((carry % radix) as u8);
+            carry /= radix;
+        }
+
+        digits.reverse();
+        Self::from_digits(&digits)
+    }
+}
+
+#[derive(Debug)]
+pub struct BigStoneParseError;
+
+impl FromStr for BigStone {
+    type Err = BigStoneParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_decimal(s, 10).ok_or(BigStoneParseError)
+    }
+}
+
+impl Display for BigStone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0
+            .iter()
+            .map(|&d| char::from_digit(d as u32, 36).unwrap_or('?'))
+            .try_for_each(|ch| write!(f, "{ch}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigStone {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(big("0"), BigStone::zero());
+        assert_eq!(big("000120").to_string(), "120");
+        assert!("".parse::<BigStone>().is_err());
+        assert!("12a".parse::<BigStone>().is_err());
+    }
+
+    #[test]
+    fn test_radix() {
+        assert_eq!(BigStone::from_decimal("255", 16).unwrap().to_string(), "ff");
+        assert_eq!(BigStone::from_decimal("5", 2).unwrap().to_string(), "101");
+        assert_eq!(BigStone::from_u64(255, 16), BigStone::from_decimal("255", 16).unwrap());
+        assert_eq!(BigStone::from_u64(0, 7), BigStone::zero());
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(big("1000").split(2), [big("10"), big("0")]);
+        assert_eq!(big("253000").split(3), [big("253"), big("0")]);
+        assert_eq!(big("28676032").split(4), [big("2867"), big("6032")]);
+        assert_eq!(big("1002").split(2), [big("10"), big("2")]);
+    }
+
+    #[test]
+    fn test_mul_add() {
+        assert_eq!(big("1").mul_add(2024, 0, 10), big("2024"));
+        assert_eq!(big("18446744073709551615").mul_add(2024, 0, 10).to_string(), (u64::MAX as u128 * 2024).to_string());
+        assert_eq!(big("123").mul_add(0, 0, 10), BigStone::zero());
+        assert_eq!(big("99").mul_add(1, 1, 10), big("100"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Overflow, StoneValue};
+use crate::rules::Rules;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct BlinkStats {
+    pub blink: usize,
+    pub total: u128,
+    pub distinct: usize
+}
+
+// the order of the stones never matters, so only count each value
+pub struct Histogram<T> {
+    rules: Rules,
+    counts: HashMap<T, u128>,
+    seen: HashSet<T>,
+    blinks: usize,
+    saturated_at: Option<usize>
+}
+
+impl<T: StoneValue> Histogram<T> {
+    pub fn new(stones: &[&str], rules: Rules) -> Result<Self, Overflow> {
+        let mut counts = HashMap::new();
+        for stone in stones {
+            let stone = T::parse(stone, rules.radix)
+                .ok_or(Overflow::Value)?;
+            *counts.entry(stone).or_insert(0) += 1;
+        }
+
+        Ok(Self {
+            rules,
+            seen: counts.keys().cloned().collect(),
+            counts,
+            blinks: 0,
+            saturated_at: None
+        })
+    }
+
+    pub fn stats(&self) -> Result<BlinkStats, Overflow> {
+        let total = self.counts
+            .values()
+            .try_fold(0u128, |total, &count| total.checked_add(count))
+            .ok_or(Overflow::Count)?;
+
+        Ok(BlinkStats {
+            blink: self.blinks,
+            total,
+            distinct: self.counts.len()
+        })
+    }
+
+    // after a blink with no new values, the set of values can't grow again
+    pub fn saturated_at(&self) -> Option<usize> {
+        self.saturated_at
+    }
+
+    pub fn seen(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn blink(&mut self) -> Result<BlinkStats, Overflow> {
+        let mut next: HashMap<T, u128> = HashMap::with_capacity(self.counts.len());
+
+        for (stone, &count) in &self.counts {
+            let (new_count, new_stones) = self.rules
+                .blink(stone)
+                .ok_or(Overflow::Value)?;
+
+            for new_stone in new_stones.into_iter().take(new_count) {
+                let entry = next.entry(new_stone).or_insert(0);
+                *entry = entry
+                    .checked_add(count)
+                    .ok_or(Overflow::Count)?;
+            }
+        }
+
+        self.blinks += 1;
+
+        let before = self.seen.len();
+        self.seen.extend(next.keys().cloned());
+        if self.saturated_at.is_none() && self.seen.len() == before {
+            self.saturated_at = Some(self.blinks);
+        }
+
+        self.counts = next;
+        self.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example() {
+        let mut histogram = Histogram::<u64>::new(&["125", "17"], Rules::default()).unwrap();
+        let totals: Vec<u128> = (0..6)
+            .map(|_| histogram.blink().unwrap().total)
+            .collect();
+        assert_eq!(totals, vec![3, 4, 5, 9, 13, 22]);
+
+        // after the 6th blink the stones are
+        // 2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2
+        assert_eq!(histogram.stats().unwrap().distinct, 15);
+    }
+
+    #[test]
+    fn test_saturation() {
+        let mut histogram = Histogram::<u64>::new(&["0"], Rules::default()).unwrap();
+        while histogram.saturated_at().is_none() {
+            histogram.blink().unwrap();
+        }
+
+        // the stones from 0 only ever take a small, fixed set of values
+        let saturated_at = histogram.saturated_at().unwrap();
+        let seen = histogram.seen();
+        for _ in 0..20 {
+            histogram.blink().unwrap();
+        }
+        assert_eq!(histogram.seen(), seen);
+        assert!(histogram.stats().unwrap().distinct <= seen);
+        assert!(saturated_at < 100);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut histogram = Histogram::<u32>::new(&["100000000"], Rules::default()).unwrap();
+        assert_eq!(histogram.blink(), Err(Overflow::Value));
+
+        let mut histogram = Histogram::<u64>::new(&["125", "17"], Rules::default()).unwrap();
+        let result = (0..300).try_for_each(|_| histogram.blink().map(|_| ()));
+        assert_eq!(result, Err(Overflow::Count));
+    }
+}
@@ -1,48 +1,204 @@
 use utils::timer::Timer;
 use std::collections::HashMap;
 use std::fs::read_to_string;
+use std::hash::Hash;
 use std::path::PathBuf;
 
-// u32 - NG - must be too small
-// u64 - works
-// u128 - also works but much slower
-type StoneValue = u64;
+mod bigstone;
+mod histogram;
+mod rules;
 
-fn blink(stone: StoneValue) -> (usize, [StoneValue; 2]) {
-    if stone == 0 {
-        return (1, [1, 0]);
+use bigstone::BigStone;
+use histogram::{BlinkStats, Histogram};
+use rules::Rules;
+
+trait StoneValue: Clone + Eq + Hash {
+    fn parse(s: &str, radix: u32) -> Option<Self>;
+    fn from_u64(value: u64, radix: u32) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+    fn digits(&self, radix: u32) -> usize;
+    fn split(&self, radix: u32, low_digits: usize) -> [Self; 2];
+    fn checked_mul(&self, factor: u64, radix: u32) -> Option<Self>;
+}
+
+// A stone that outgrows one of these types is reported as an overflow rather
+// than wrapping. The counts try `u64` first, as it fits the puzzle's stones.
+macro_rules! impl_stone_value {
+    ($($t:ty),*) => {
+        $(
+            impl StoneValue for $t {
+                fn parse(s: &str, _radix: u32) -> Option<Self> {
+                    s.parse().ok()
+                }
+
+                fn from_u64(value: u64, _radix: u32) -> Option<Self> {
+                    Self::try_from(value).ok()
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+
+                fn digits(&self, radix: u32) -> usize {
+                    self.checked_ilog(radix as $t).map_or(1, |log| log as usize + 1)
+                }
+
+                fn split(&self, radix: u32, low_digits: usize) -> [Self; 2] {
+                    let divisor = (radix as $t).pow(low_digits as u32);
+                    [self / divisor, self % divisor]
+                }
+
+                fn checked_mul(&self, factor: u64, _radix: u32) -> Option<Self> {
+                    <$t>::checked_mul(*self, Self::try_from(factor).ok()?)
+                }
+            }
+        )*
+    };
+}
+
+impl_stone_value!(u32, u64, u128);
+
+impl StoneValue for BigStone {
+    fn parse(s: &str, radix: u32) -> Option<Self> {
+        BigStone::from_decimal(s, radix)
     }
-    else {
-        let digits = stone.ilog10() + 1;
-        if digits % 2 == 0 {
-            let divisor = (10 as StoneValue).pow(digits / 2);
-            return (2, [stone / divisor, stone % divisor]);
-        }
+
+    fn from_u64(value: u64, radix: u32) -> Option<Self> {
+        Some(BigStone::from_u64(value, radix))
+    }
+
+    fn is_zero(&self) -> bool {
+        BigStone::is_zero(self)
+    }
+
+    fn digits(&self, _radix: u32) -> usize {
+        BigStone::digits(self)
+    }
+
+    fn split(&self, _radix: u32, low_digits: usize) -> [Self; 2] {
+        BigStone::split(self, low_digits)
     }
 
-    (1, [stone * 2024, 0])
+    fn checked_mul(&self, factor: u64, radix: u32) -> Option<Self> {
+        Some(self.mul_add(factor, 0, radix))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Overflow {
+    Value,
+    Count
+}
+
+struct Frame<T> {
+    stone: T,
+    blinks: usize,
+    pending: Vec<T>,
+    total: u128
 }
 
-fn count_stones_for_blinks(stone: StoneValue, blinks: usize, cache: &mut HashMap<(usize, StoneValue),usize>) -> usize {
-    if let Some(result) = cache.get(&(blinks, stone)) {
-        return *result;
+impl<T: StoneValue> Frame<T> {
+    fn new(stone: T, blinks: usize, rules: &Rules) -> Result<Self, Overflow> {
+        let (count, new_stones) = rules
+            .blink(&stone)
+            .ok_or(Overflow::Value)?;
+
+        Ok(Self {
+            stone,
+            blinks,
+            pending: new_stones.into_iter().take(count).collect(),
+            total: 0
+        })
+    }
+}
+
+// an explicit stack, since thousands of blinks would overflow the call stack
+fn count_stones_for_blinks<T: StoneValue>(stone: &T, blinks: usize, rules: &Rules, cache: &mut HashMap<(usize, T), u128>) -> Result<u128, Overflow> {
+    if blinks == 0 {
+        return Ok(1);
+    }
+
+    if let Some(result) = cache.get(&(blinks, stone.clone())) {
+        return Ok(*result);
     }
 
-    let (count, new_stones) = blink(stone);
-    if blinks == 1 {
-        cache.insert((blinks, stone), count);
-        return count;
+    let mut stack = vec![Frame::new(stone.clone(), blinks, rules)?];
+    let mut returned = None;
+
+    while let Some(frame) = stack.last_mut() {
+        if let Some(count) = returned.take() {
+            frame.total = frame.total
+                .checked_add(count)
+                .ok_or(Overflow::Count)?;
+        }
+
+        match frame.pending.pop() {
+            Some(_) if frame.blinks == 1 => returned = Some(1),
+            Some(next) => match cache.get(&(frame.blinks - 1, next.clone())) {
+                Some(&count) => returned = Some(count),
+                None => {
+                    let next = Frame::new(next, frame.blinks - 1, rules)?;
+                    stack.push(next);
+                }
+            },
+            None => {
+                let done = stack
+                    .pop()
+                    .expect("Stack should not be empty");
+                cache.insert((done.blinks, done.stone), done.total);
+                returned = Some(done.total);
+            }
+        }
     }
 
-    let result = new_stones
+    Ok(returned.expect("Should have counted the first stone"))
+}
+
+fn count_stones<T: StoneValue>(stones: &[&str], blinks: usize, rules: &Rules) -> Result<u128, Overflow> {
+    let mut cache = HashMap::new();
+    stones
         .iter()
-        .take(count)
-        .map(|stone| count_stones_for_blinks(*stone, blinks - 1, cache))
-        .sum();
+        .try_fold(0u128, |total, stone| {
+            let stone = T::parse(stone, rules.radix)
+                .ok_or(Overflow::Value)?;
+            total
+                .checked_add(count_stones_for_blinks(&stone, blinks, rules, &mut cache)?)
+                .ok_or(Overflow::Count)
+        })
+}
 
-    cache.insert((blinks, stone), result);
+fn count_stones_checked(stones: &[&str], blinks: usize, rules: &Rules) -> Result<u128, Overflow> {
+    match count_stones::<u64>(stones, blinks, rules) {
+        Err(Overflow::Value) => count_stones::<BigStone>(stones, blinks, rules),
+        result => result
+    }
+}
+
+struct Evolution {
+    stats: Vec<BlinkStats>,
+    saturated_at: Option<usize>,
+    seen: usize
+}
+
+fn evolve<T: StoneValue>(stones: &[&str], blinks: usize, rules: &Rules) -> Result<Evolution, Overflow> {
+    let mut histogram = Histogram::<T>::new(stones, rules.clone())?;
+    let mut stats = vec![histogram.stats()?];
+    for _ in 0..blinks {
+        stats.push(histogram.blink()?);
+    }
 
-    result
+    Ok(Evolution {
+        stats,
+        saturated_at: histogram.saturated_at(),
+        seen: histogram.seen()
+    })
+}
+
+fn evolve_checked(stones: &[&str], blinks: usize, rules: &Rules) -> Result<Evolution, Overflow> {
+    match evolve::<u64>(stones, blinks, rules) {
+        Err(Overflow::Value) => evolve::<BigStone>(stones, blinks, rules),
+        result => result
+    }
 }
 
 fn main() {
@@ -53,20 +209,135 @@ fn main() {
         .expect("Should have file argument")
         .into();
 
-    let stones = read_to_string(path)
-        .expect("Should be able to read from path")
+    let mut blinks = if cfg!(feature = "part2") { 75 } else { 25 };
+    let mut stats = false;
+    let mut rules = Rules::default();
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--blinks" => blinks = args
+                .next()
+                .and_then(|b| b.parse().ok())
+                .expect("Should have a number of blinks after --blinks"),
+            "--stats" => stats = true,
+            "--rules" => {
+                let config = args
+                    .next()
+                    .expect("Should have rules after --rules");
+                rules = config
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Rules should be valid: {e}"));
+            },
+            _ => panic!("Unknown option: {arg}")
+        }
+    }
+
+    let input = read_to_string(path)
+        .expect("Should be able to read from path");
+
+    let stones: Vec<&str> = input
         .split_ascii_whitespace()
-        .map(|s| s.parse())
-        .collect::<Result<Vec<StoneValue>,_>>()
-        .expect("Numbers in input should be parsable");
+        .collect();
+    assert!(
+        stones.iter().all(|s| s.bytes().all(|b| b.is_ascii_digit())),
+        "Numbers in input should be parsable"
+    );
 
-    let mut cache = HashMap::new();
-    let mut answer = 0;
-    let blinks = if cfg!(feature = "part2") { 75 } else { 25 };
+    let answer = if stats {
+        let evolution = evolve_checked(&stones, blinks, &rules)
+            .expect("Number of stones should fit in a u128");
+
+        for s in &evolution.stats {
+            println!("blink {:>4}: {:>40} stones, {:>6} distinct", s.blink, s.total, s.distinct);
+        }
 
-    for stone in stones {
-        answer += count_stones_for_blinks(stone, blinks, &mut cache);
+        match evolution.saturated_at {
+            Some(blink) => println!("No new values appeared from blink {blink} onwards, {} in all", evolution.seen),
+            None => println!("New values were still appearing at blink {blinks}, {} so far", evolution.seen)
+        }
+
+        evolution.stats[blinks].total
     }
+    else {
+        count_stones_checked(&stones, blinks, &rules)
+            .expect("Number of stones should fit in a u128")
+    };
 
     println!("Answer: {answer}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example() {
+        assert_eq!(count_stones::<u64>(&["0", "1", "10", "99", "999"], 1, &Rules::default()), Ok(7));
+        assert_eq!(count_stones::<u64>(&["125", "17"], 6, &Rules::default()), Ok(22));
+        assert_eq!(count_stones::<u64>(&["125", "17"], 25, &Rules::default()), Ok(55312));
+    }
+
+    #[test]
+    fn test_value_types_agree() {
+        let expected = count_stones::<u64>(&["125", "17"], 75, &Rules::default());
+        assert_eq!(count_stones::<u128>(&["125", "17"], 75, &Rules::default()), expected);
+        assert_eq!(count_stones::<BigStone>(&["125", "17"], 75, &Rules::default()), expected);
+
+        assert_eq!(count_stones::<u32>(&["125", "17"], 75, &Rules::default()), expected);
+        assert_eq!(count_stones::<u32>(&["100000000"], 1, &Rules::default()), Err(Overflow::Value));
+    }
+
+    #[test]
+    fn test_large_stones() {
+        let huge = "123456789012345678901234567";
+        assert_eq!(count_stones::<u64>(&[huge], 5, &Rules::default()), Err(Overflow::Value));
+        assert_eq!(count_stones_checked(&[huge], 1, &Rules::default()), Ok(1));
+        assert_eq!(count_stones_checked(&[huge], 2, &Rules::default()), Ok(2));
+
+        // 10^16 has an odd number of digits, and 2024 * 10^16 overflows u64
+        let overflowing = 10u64.pow(16).to_string();
+        assert_eq!(count_stones::<u64>(&[&overflowing], 1, &Rules::default()), Err(Overflow::Value));
+        assert_eq!(count_stones_checked(&[&overflowing], 1, &Rules::default()), Ok(1));
+        assert_eq!(count_stones_checked(&[&overflowing], 2, &Rules::default()), count_stones::<u128>(&[&overflowing], 2, &Rules::default()));
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        let evolution = evolve_checked(&["125", "17"], 75, &Rules::default()).unwrap();
+        for blinks in [0, 1, 6, 25, 75] {
+            assert_eq!(Ok(evolution.stats[blinks].total), count_stones_checked(&["125", "17"], blinks, &Rules::default()));
+        }
+
+        let huge = "123456789012345678901234567";
+        let evolution = evolve_checked(&[huge, "0"], 30, &Rules::default()).unwrap();
+        assert_eq!(Ok(evolution.stats[30].total), count_stones_checked(&[huge, "0"], 30, &Rules::default()));
+    }
+
+    #[test]
+    fn test_other_rules() {
+        // both engines agree under other rules as well
+        let rules: Rules = "zero=1, split, multiply=3, radix=2".parse().unwrap();
+        let evolution = evolve_checked(&["125", "17"], 40, &rules).unwrap();
+        for blinks in [1, 10, 40] {
+            assert_eq!(Ok(evolution.stats[blinks].total), count_stones_checked(&["125", "17"], blinks, &rules));
+        }
+
+        // no rules, so the stones never change
+        let rules: Rules = "".parse().unwrap();
+        assert_eq!(count_stones_checked(&["1", "2", "3"], 100, &rules), Ok(3));
+
+        // huge stones are kept in the rules' radix too
+        let rules: Rules = "zero=1, split, multiply=2024, radix=16".parse().unwrap();
+        let huge = "123456789012345678901234567";
+        assert_eq!(
+            count_stones::<BigStone>(&[huge], 20, &rules),
+            evolve::<BigStone>(&[huge], 20, &rules).map(|e| e.stats[20].total)
+        );
+    }
+
+    #[test]
+    fn test_count_overflow() {
+        assert!(count_stones_checked(&["125", "17"], 200, &Rules::default()).is_ok());
+        assert_eq!(count_stones_checked(&["125", "17"], 3000, &Rules::default()), Err(Overflow::Count));
+    }
+}
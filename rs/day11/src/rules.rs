@@ -0,0 +1,181 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::StoneValue;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    Zero(u64),
+    Split,
+    Multiply(u64)
+}
+
+// the first rule that applies to a stone is used; if none do it stays as it is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+    pub radix: u32,
+    pub rules: Vec<Rule>
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            radix: 10,
+            rules: vec![Rule::Zero(1), Rule::Split, Rule::Multiply(2024)]
+        }
+    }
+}
+
+impl Rules {
+    pub fn blink<T: StoneValue>(&self, stone: &T) -> Option<(usize, [T; 2])> {
+        for rule in &self.rules {
+            match *rule {
+                Rule::Zero(replacement) => if stone.is_zero() {
+                    return Some((1, [T::from_u64(replacement, self.radix)?, stone.clone()]));
+                },
+                Rule::Split => {
+                    let digits = stone.digits(self.radix);
+                    if digits.is_multiple_of(2) {
+                        return Some((2, stone.split(self.radix, digits / 2)));
+                    }
+                },
+                Rule::Multiply(factor) => {
+                    return Some((1, [stone.checked_mul(factor, self.radix)?, stone.clone()]));
+                }
+            }
+        }
+
+        Some((1, [stone.clone(), stone.clone()]))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RulesParseError {
+    UnknownRule(String),
+    InvalidNumber(String),
+    InvalidRadix(u32)
+}
+
+impl Display for RulesParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RulesParseError::UnknownRule(rule) =>
+                write!(f, "unknown rule {rule:?}, expected zero=N, split, multiply=N or radix=N"),
+            RulesParseError::InvalidNumber(rule) =>
+                write!(f, "invalid number in {rule:?}"),
+            RulesParseError::InvalidRadix(radix) =>
+                write!(f, "radix {radix} is not between 2 and 36")
+        }
+    }
+}
+
+impl FromStr for Rules {
+    type Err = RulesParseError;
+
+    // e.g. `zero=1, split, multiply=2024, radix=10`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut radix = 10;
+        let mut rules = Vec::new();
+
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (name, value) = match item.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (item, None)
+            };
+
+            let number = || value
+                .and_then(|v| v.parse::<u64>().ok())
+                .ok_or_else(|| RulesParseError::InvalidNumber(item.to_string()));
+
+            match (name, value) {
+                ("zero", _) => rules.push(Rule::Zero(number()?)),
+                ("split", None) => rules.push(Rule::Split),
+                ("multiply", _) => rules.push(Rule::Multiply(number()?)),
+                ("radix", _) => {
+                    let r = number()?;
+                    radix = u32::try_from(r)
+                        .ok()
+                        .filter(|r| (2..=36).contains(r))
+                        .ok_or(RulesParseError::InvalidRadix(r.min(u32::MAX as u64) as u32))?;
+                },
+                _ => return Err(RulesParseError::UnknownRule(item.to_string()))
+            }
+        }
+
+        Ok(Self {
+            radix,
+            rules
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigstone::BigStone;
+
+    fn rules(s: &str) -> Rules {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(rules("zero=1, split, multiply=2024"), Rules::default());
+        assert_eq!(rules(" radix = 16 ,split"), Rules { radix: 16, rules: vec![Rule::Split] });
+        assert_eq!("zero".parse::<Rules>(), Err(RulesParseError::InvalidNumber("zero".to_string())));
+        assert_eq!("split=2".parse::<Rules>(), Err(RulesParseError::UnknownRule("split=2".to_string())));
+        assert_eq!("divide=2".parse::<Rules>(), Err(RulesParseError::UnknownRule("divide=2".to_string())));
+        assert_eq!("radix=1".parse::<Rules>(), Err(RulesParseError::InvalidRadix(1)));
+    }
+
+    #[test]
+    fn test_zero() {
+        let r = rules("zero=7");
+        assert_eq!(r.blink(&0u64), Some((1, [7, 0])));
+        assert_eq!(r.blink(&5u64), Some((1, [5, 5])));
+    }
+
+    #[test]
+    fn test_split() {
+        let r = rules("split");
+        assert_eq!(r.blink(&1234u64), Some((2, [12, 34])));
+        assert_eq!(r.blink(&1000u64), Some((2, [10, 0])));
+        assert_eq!(r.blink(&123u64).map(|(count, _)| count), Some(1));
+
+        // 0xab12 has four digits in hex but five in decimal
+        let r = rules("split, radix=16");
+        assert_eq!(r.blink(&0xab12u64), Some((2, [0xab, 0x12])));
+        let r = rules("split, radix=2");
+        assert_eq!(r.blink(&0b1101u64), Some((2, [0b11, 0b01])));
+    }
+
+    #[test]
+    fn test_multiply() {
+        let r = rules("multiply=3");
+        assert_eq!(r.blink(&5u64), Some((1, [15, 5])));
+        assert_eq!(r.blink(&u64::MAX), None);
+    }
+
+    #[test]
+    fn test_order() {
+        // multiplying first means nothing ever splits
+        let r = rules("multiply=2024, split");
+        assert_eq!(r.blink(&12u64), Some((1, [24288, 12])));
+        let r = rules("split, multiply=2024");
+        assert_eq!(r.blink(&12u64), Some((2, [1, 2])));
+    }
+
+    #[test]
+    fn test_big_stones_agree() {
+        for r in [Rules::default(), rules("zero=3, split, multiply=7, radix=16"), rules("split, zero=1, multiply=5, radix=3")] {
+            for value in [0u64, 1, 12, 255, 4096, 99999, 123456] {
+                let (count, small) = r.blink(&value).unwrap();
+                let (big_count, big) = r.blink(&BigStone::from_u64(value, r.radix)).unwrap();
+                assert_eq!(count, big_count);
+                for i in 0..count {
+                    assert_eq!(BigStone::from_u64(small[i], r.radix), big[i]);
+                }
+            }
+        }
+    }
+}
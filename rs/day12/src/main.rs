@@ -4,10 +4,12 @@ use std::path::PathBuf;
 
 struct Farm<'a> {
     map: &'a [u8],
-    width: usize
+    // distance from one row to the next, including any newline
+    width: usize,
+    row_len: usize
 }
 
-const EMPTY: u16 = 0;
+const EMPTY: u32 = 0;
 
 const NEIGHBOUR_UP: u16 = 0b0001;
 const NEIGHBOUR_RIGHT: u16 = 0b0010;
@@ -16,7 +18,7 @@ const NEIGHBOUR_LEFT: u16 = 0b1000;
 
 #[derive(Clone,Debug)]
 struct Cell {
-    id: u16,
+    id: u32,
     neighbours: u16,
 }
 
@@ -29,54 +31,85 @@ impl Default for Cell {
     }
 }
 
+// the bounding box is inclusive [min_x, min_y, max_x, max_y]
+#[derive(Debug)]
+struct Region {
+    plant: u8,
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+    bounds: [usize; 4],
+    holes: usize
+}
+
+impl Region {
+    fn price(&self) -> usize {
+        self.area * self.perimeter
+    }
+
+    fn bulk_price(&self) -> usize {
+        self.area * self.sides
+    }
+}
+
 impl<'a> From<&'a [u8]> for Farm<'a> {
     fn from(map: &'a [u8]) -> Self {
-        let width = map
+        let newline = map
             .iter()
-            .position(|ch| *ch == b'\n')
+            .position(|ch| *ch == b'\n');
+        let row_len = newline.unwrap_or(map.len());
+        let width = newline
             .map(|w| w + 1)
             .unwrap_or(map.len());
 
         Farm {
             map,
-            width
+            width,
+            row_len
         }
     }
 }
 
 impl Farm<'_> {
-    fn find_plot(&self, current_pos: usize, id: u16, analysis: &mut [Cell]) {
-        let plot_name = self.map[current_pos];
-        analysis[current_pos].id = id;
-        analysis[current_pos].neighbours =
-            [
-                (NEIGHBOUR_LEFT, -1), 
-                (NEIGHBOUR_RIGHT, 1),
-                (NEIGHBOUR_DOWN, self.width as isize),
-                (NEIGHBOUR_UP, -(self.width as isize))
-            ]
-            .iter()
-            .filter_map(|next|
-                current_pos
-                    .checked_add_signed(next.1)
+    // an explicit stack, so that very large regions can't overflow the call stack
+    fn find_plot(&self, start: usize, id: u32, analysis: &mut [Cell]) {
+        let plot_name = self.map[start];
+        let offsets = [
+            (NEIGHBOUR_LEFT, -1),
+            (NEIGHBOUR_RIGHT, 1),
+            (NEIGHBOUR_DOWN, self.width as isize),
+            (NEIGHBOUR_UP, -(self.width as isize))
+        ];
+
+        analysis[start].id = id;
+        let mut stack = vec![start];
+
+        while let Some(current_pos) = stack.pop() {
+            let mut neighbours = 0;
+
+            for (neighbour_bitmask, offset) in offsets {
+                let Some(next_pos) = current_pos
+                    .checked_add_signed(offset)
                     .filter(|next_pos| *next_pos < self.map.len() && self.map[*next_pos] == plot_name)
-                    .map(|next_pos| (next.0, next_pos))
-            )
-            .fold(0u16, |acc, (neighbour_bitmask, next_pos)| {
-                if analysis[next_pos].id == EMPTY {
-                    self.find_plot(next_pos, id, analysis);
-                }
-                
-                if analysis[next_pos].id == id {
-                    acc | neighbour_bitmask
-                }
                 else {
-                    acc
+                    continue;
+                };
+
+                // an adjacent cell with the same plant is always in the same
+                // region
+                neighbours |= neighbour_bitmask;
+
+                if analysis[next_pos].id == EMPTY {
+                    analysis[next_pos].id = id;
+                    stack.push(next_pos);
                 }
-            });
+            }
+
+            analysis[current_pos].neighbours = neighbours;
+        }
     }
-    
-    fn identify_plots(&self, analysis: &mut [Cell]) -> u16 {
+
+    fn identify_plots(&self, analysis: &mut [Cell]) -> u32 {
         let mut i = 0;
         let mut id = 0;
         while i < self.map.len() {
@@ -96,23 +129,28 @@ impl Farm<'_> {
     fn count_fence_runs(&self, analysis: &[Cell], fence_runs: &mut [usize], area_tally: &mut [usize]) {
         // go from left to right, looking for fence runs
         // the '\n' character in the stream actually helps here since it
-        // automatically terminates our run-length detection
-        for x in 0..analysis.len() - 1 {
+        // automatically terminates our run-length detection, and the end of
+        // the map does the same when there is no trailing newline
+        for x in 0..analysis.len() {
             if analysis[x].id == EMPTY {
                 continue;
             }
 
             area_tally[analysis[x].id as usize] += 1;
 
+            let next = analysis
+                .get(x + 1)
+                .filter(|next| next.id == analysis[x].id);
+
             let this_fence_up = analysis[x].neighbours & NEIGHBOUR_UP == 0;
-            let next_fence_up = analysis[x+1].neighbours & NEIGHBOUR_UP == 0;
-            if this_fence_up && !(analysis[x].id == analysis[x+1].id && next_fence_up) {
+            let next_fence_up = next.is_some_and(|next| next.neighbours & NEIGHBOUR_UP == 0);
+            if this_fence_up && !next_fence_up {
                 fence_runs[analysis[x].id as usize] += 1;
             }
 
             let this_fence_down = analysis[x].neighbours & NEIGHBOUR_DOWN == 0;
-            let next_fence_down = analysis[x+1].neighbours & NEIGHBOUR_DOWN == 0;
-            if this_fence_down && !(analysis[x].id == analysis[x+1].id && next_fence_down) {
+            let next_fence_down = next.is_some_and(|next| next.neighbours & NEIGHBOUR_DOWN == 0);
+            if this_fence_down && !next_fence_down {
                 fence_runs[analysis[x].id as usize] += 1;
             }
         }
@@ -149,6 +187,149 @@ impl Farm<'_> {
     }
 }
 
+impl Farm<'_> {
+    fn height(&self) -> usize {
+        self.map.len().div_ceil(self.width)
+    }
+
+    fn id_at(&self, analysis: &[Cell], x: usize, y: usize) -> u32 {
+        if x >= self.row_len {
+            return EMPTY;
+        }
+        analysis
+            .get(y * self.width + x)
+            .map_or(EMPTY, |cell| cell.id)
+    }
+
+    fn id_offset(&self, analysis: &[Cell], x: usize, y: usize, dx: isize, dy: isize) -> u32 {
+        match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+            (Some(x), Some(y)) => self.id_at(analysis, x, y),
+            _ => EMPTY
+        }
+    }
+
+    // a polygon has as many sides as corners; comparing region ids rather
+    // than plants keeps regions that only touch diagonally apart
+    fn count_corners(&self, analysis: &[Cell], corners: &mut [usize]) {
+        for (pos, cell) in analysis.iter().enumerate() {
+            if cell.id == EMPTY {
+                continue;
+            }
+
+            let (x, y) = (pos % self.width, pos / self.width);
+            for (dx, dy) in [(-1, -1), (1, -1), (1, 1), (-1, 1)] {
+                let horizontal = self.id_offset(analysis, x, y, dx, 0) == cell.id;
+                let vertical = self.id_offset(analysis, x, y, 0, dy) == cell.id;
+                let diagonal = self.id_offset(analysis, x, y, dx, dy) == cell.id;
+
+                if (!horizontal && !vertical) || (horizontal && vertical && !diagonal) {
+                    corners[cell.id as usize] += 1;
+                }
+            }
+        }
+    }
+
+    // Euler's formula for a region of unit squares: V - E + F = 1 - holes,
+    // with F the area and E = (4 * area + perimeter) / 2
+    fn count_holes(&self, analysis: &[Cell], regions: &mut [Region]) {
+        let mut vertices = vec![0usize; regions.len()];
+
+        for vy in 0..=self.height() {
+            for vx in 0..=self.row_len {
+                let mut around = [EMPTY; 4];
+                for (i, (dx, dy)) in [(1, 1), (0, 1), (1, 0), (0, 0)].into_iter().enumerate() {
+                    if let (Some(x), Some(y)) = (vx.checked_sub(dx), vy.checked_sub(dy)) {
+                        around[i] = self.id_at(analysis, x, y);
+                    }
+                }
+
+                for i in 0..4 {
+                    if around[i] != EMPTY && !around[..i].contains(&around[i]) {
+                        vertices[around[i] as usize - 1] += 1;
+                    }
+                }
+            }
+        }
+
+        for (region, v) in regions.iter_mut().zip(vertices) {
+            let edges = (4 * region.area + region.perimeter) / 2;
+            region.holes = 1 + edges - v - region.area;
+        }
+    }
+
+    fn regions(&self) -> Vec<Region> {
+        let mut analysis = vec![Cell::default(); self.map.len()];
+        let max_id = self.identify_plots(&mut analysis) as usize;
+
+        let mut fence_runs = vec![0usize; max_id + 1];
+        let mut area_tally = vec![0usize; max_id + 1];
+        self.count_fence_runs(&analysis, &mut fence_runs, &mut area_tally);
+
+        if cfg!(debug_assertions) {
+            let mut corners = vec![0usize; max_id + 1];
+            self.count_corners(&analysis, &mut corners);
+            debug_assert_eq!(corners, fence_runs, "Corners and fence runs should agree");
+        }
+
+        let mut regions: Vec<Region> = (0..max_id)
+            .map(|_| Region {
+                plant: 0,
+                area: 0,
+                perimeter: 0,
+                sides: 0,
+                bounds: [usize::MAX, usize::MAX, 0, 0],
+                holes: 0
+            })
+            .collect();
+
+        for (pos, cell) in analysis.iter().enumerate() {
+            if cell.id == EMPTY {
+                continue;
+            }
+
+            let (x, y) = (pos % self.width, pos / self.width);
+            let region = &mut regions[cell.id as usize - 1];
+            region.plant = self.map[pos];
+            region.area += 1;
+            region.perimeter += (4 - cell.neighbours.count_ones()) as usize;
+            region.bounds = [
+                region.bounds[0].min(x),
+                region.bounds[1].min(y),
+                region.bounds[2].max(x),
+                region.bounds[3].max(y)
+            ];
+        }
+
+        for (region, sides) in regions.iter_mut().zip(&fence_runs[1..]) {
+            region.sides = *sides;
+        }
+
+        self.count_holes(&analysis, &mut regions);
+        regions
+    }
+}
+
+fn print_report(regions: &[Region], csv: bool) {
+    if csv {
+        println!("plant,area,perimeter,sides,min_x,min_y,max_x,max_y,holes,price,bulk_price");
+    }
+    else {
+        println!("{:<5} {:>8} {:>9} {:>7} {:>23} {:>5} {:>12} {:>12}", "plant", "area", "perimeter", "sides", "bounds", "holes", "price", "bulk price");
+    }
+
+    for r in regions {
+        let [min_x, min_y, max_x, max_y] = r.bounds;
+        let plant = r.plant as char;
+        if csv {
+            println!("{plant},{},{},{},{min_x},{min_y},{max_x},{max_y},{},{},{}", r.area, r.perimeter, r.sides, r.holes, r.price(), r.bulk_price());
+        }
+        else {
+            let bounds = format!("({min_x},{min_y})-({max_x},{max_y})");
+            println!("{plant:<5} {:>8} {:>9} {:>7} {bounds:>23} {:>5} {:>12} {:>12}", r.area, r.perimeter, r.sides, r.holes, r.price(), r.bulk_price());
+        }
+    }
+}
+
 fn main() {
     let _timer = Timer::new();
 
@@ -162,36 +343,35 @@ fn main() {
     
     let farm: Farm = data.as_slice().into();
 
-    let mut analysis = vec![Cell::default(); farm.map.len()];
-    let max_id = farm.identify_plots(&mut analysis);
+    let regions = farm.regions();
 
-    let answer: usize = if cfg!(feature = "part2") {
-        let mut fence_runs = vec![0usize; max_id as usize + 1];
-        let mut area_tally = vec![0usize; max_id as usize + 1];
+    let mut report = false;
+    let mut csv = false;
+    for arg in std::env::args().skip(2) {
+        match arg.as_str() {
+            "--report" => report = true,
+            "--csv" => {
+                report = true;
+                csv = true;
+            },
+            _ => panic!("Unknown option: {arg}")
+        }
+    }
 
-        farm.count_fence_runs(&analysis, &mut fence_runs, &mut area_tally);
+    if report {
+        print_report(&regions, csv);
+    }
 
-        area_tally
+    let answer: usize = if cfg!(feature = "part2") {
+        regions
             .iter()
-            .zip(fence_runs.iter())
-            .map(|(area, fence_runs)| area * fence_runs)
+            .map(Region::bulk_price)
             .sum()
     }
     else {
-        let mut fence_tally = vec![0usize; max_id as usize + 1];
-        let mut area_tally = vec![0usize; max_id as usize + 1];
-        for cell in &analysis {
-            if cell.id == EMPTY {
-                continue;
-            }
-            fence_tally[cell.id as usize] += (4 - cell.neighbours.count_ones()) as usize;
-            area_tally[cell.id as usize] += 1;
-        }
-
-        area_tally
+        regions
             .iter()
-            .zip(fence_tally.iter())
-            .map(|(area, fence)| area * fence)
+            .map(Region::price)
             .sum()
     };
     
@@ -217,4 +397,162 @@ mod tests {
         assert_eq!(area_tally[1], 5);
         assert_eq!(area_tally[2], 5);
     }
+
+    const EXAMPLE: &[u8] = b"\
+RRRRIICCFF
+RRRRIICCCF
+VVRRRCCFFF
+VVRCCCJFFF
+VVVVCJJCFE
+VVIVCCJJEE
+VVIIICJJEE
+MIIIIIJJEE
+MIIISIJEEE
+MMMISSJEEE
+";
+
+    #[test]
+    fn test_example_regions() {
+        let regions = Farm::from(EXAMPLE).regions();
+
+        let summary: Vec<(char, usize, usize, usize)> = regions
+            .iter()
+            .map(|r| (r.plant as char, r.area, r.price(), r.bulk_price()))
+            .collect();
+
+        assert_eq!(summary, vec![
+            ('R', 12, 216, 120),
+            ('I', 4, 32, 16),
+            ('C', 14, 392, 308),
+            ('F', 10, 180, 120),
+            ('V', 13, 260, 130),
+            ('J', 11, 220, 132),
+            ('C', 1, 4, 4),
+            ('E', 13, 234, 104),
+            ('I', 14, 308, 224),
+            ('M', 5, 60, 30),
+            ('S', 3, 24, 18)
+        ]);
+
+        assert_eq!(regions[0].bounds, [0, 0, 4, 3]);
+        assert!(regions.iter().all(|r| r.holes == 0));
+    }
+
+    const SIDES_CORPUS: &[(&str, usize)] = &[
+        ("AAAA\nBBBD\nBBCC\nEEEC\n", 80),
+        ("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO\n", 436),
+        ("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE\n", 236),
+        ("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA\n", 368),
+        ("AB\nBA\n", 16),
+        // two L shaped A regions meeting only at corners, and three single Bs
+        ("AAB\nABA\nBAA\n", 2 * 3 * 6 + 3 * 4),
+        // a single row, which has no newline column at all when trimmed
+        ("AA\n", 2 * 4),
+        ("AAB\n", 2 * 4 + 4)
+    ];
+
+    #[test]
+    fn test_corners_match_fence_runs() {
+        let large = std::str::from_utf8(EXAMPLE).unwrap();
+        let corpus = SIDES_CORPUS
+            .iter()
+            .copied()
+            .chain(std::iter::once((large, 1206)));
+
+        for (map, expected) in corpus {
+            for map in [map, map.trim_end()] {
+                let farm = Farm::from(map.as_bytes());
+                let mut analysis = vec![Cell::default(); farm.map.len()];
+                let max_id = farm.identify_plots(&mut analysis) as usize;
+
+                let mut fence_runs = vec![0usize; max_id + 1];
+                let mut area_tally = vec![0usize; max_id + 1];
+                farm.count_fence_runs(&analysis, &mut fence_runs, &mut area_tally);
+
+                let mut corners = vec![0usize; max_id + 1];
+                farm.count_corners(&analysis, &mut corners);
+
+                assert_eq!(corners, fence_runs, "{map:?}");
+
+                let price: usize = area_tally
+                    .iter()
+                    .zip(&corners)
+                    .map(|(area, sides)| area * sides)
+                    .sum();
+                assert_eq!(price, expected, "{map:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_holes() {
+        let regions = Farm::from(b"OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO\n".as_slice()).regions();
+        assert_eq!(regions[0].holes, 4);
+        assert!(regions[1..].iter().all(|r| r.holes == 0));
+
+        // the two B regions touch diagonally but are still separate holes
+        let regions = Farm::from(b"AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA\n".as_slice()).regions();
+        assert_eq!(regions[0].holes, 2);
+        assert_eq!(regions[0].bulk_price(), 28 * 12);
+
+        // a hole may hold several regions
+        let regions = Farm::from(b"AAAA\nABCA\nAAAA\n".as_slice()).regions();
+        assert_eq!(regions[0].holes, 1);
+
+        // a ring that is only closed diagonally doesn't enclose anything
+        let regions = Farm::from(b"BAB\nABA\nBAB\n".as_slice()).regions();
+        assert!(regions.iter().all(|r| r.holes == 0));
+
+        // without a trailing newline there is no newline column to the right
+        // of the last plot
+        for (map, holes) in [(b"AA".as_slice(), 0), (b"AAA", 0), (b"AAB", 0), (b"AAAA\nABBA\nAAAA", 1)] {
+            let regions = Farm::from(map).regions();
+            assert_eq!(regions[0].holes, holes, "{:?}", String::from_utf8_lossy(map));
+            assert!(regions[1..].iter().all(|r| r.holes == 0));
+        }
+    }
+
+    #[test]
+    fn test_many_regions() {
+        // a checkerboard makes every cell its own region, more than fit in a
+        // u16
+        let size = 300;
+        let map: Vec<u8> = (0..size)
+            .flat_map(|y| (0..size)
+                .map(move |x| if (x + y) % 2 == 0 { b'A' } else { b'B' })
+                .chain(std::iter::once(b'\n')))
+            .collect();
+
+        let farm = Farm::from(map.as_slice());
+        let mut analysis = vec![Cell::default(); farm.map.len()];
+        assert_eq!(farm.identify_plots(&mut analysis), size * size);
+        assert!(analysis.iter().all(|cell| cell.neighbours == 0));
+    }
+
+    #[test]
+    fn test_huge_region() {
+        let size = 5000;
+        let mut map = vec![b'A'; (size + 1) * size];
+        for row in map.chunks_mut(size + 1) {
+            row[size] = b'\n';
+        }
+
+        let farm = Farm::from(map.as_slice());
+        let mut analysis = vec![Cell::default(); farm.map.len()];
+        let max_id = farm.identify_plots(&mut analysis);
+        assert_eq!(max_id, 1);
+
+        let perimeter: usize = analysis
+            .iter()
+            .filter(|cell| cell.id != EMPTY)
+            .map(|cell| (4 - cell.neighbours.count_ones()) as usize)
+            .sum();
+        assert_eq!(perimeter, 4 * size);
+
+        let mut fence_runs = vec![0usize; 2];
+        let mut area_tally = vec![0usize; 2];
+        farm.count_fence_runs(&analysis, &mut fence_runs, &mut area_tally);
+        assert_eq!(area_tally[1], size * size);
+        assert_eq!(fence_runs[1], 4);
+    }
 }
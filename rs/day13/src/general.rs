@@ -0,0 +1,454 @@
+use std::fmt::Display;
+
+use crate::extended_gcd;
+
+const SEARCH_LIMIT: i128 = 10_000_000;
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+// always in lowest terms with a positive denominator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rational {
+    num: i128,
+    den: i128
+}
+
+impl Rational {
+    const ZERO: Self = Self { num: 0, den: 1 };
+
+    fn new(num: i128, den: i128) -> Self {
+        let g = gcd(num, den) * den.signum();
+        Self {
+            num: num / g,
+            den: den / g
+        }
+    }
+
+    fn integer(value: i64) -> Self {
+        Self::new(value as i128, 1)
+    }
+
+    fn recip(self) -> Self {
+        Self::new(self.den, self.num)
+    }
+
+    fn as_integer(self) -> Option<i128> {
+        (self.den == 1).then_some(self.num)
+    }
+
+    fn floor(self) -> i128 {
+        self.num.div_euclid(self.den)
+    }
+
+    fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let g = gcd(self.den, rhs.den);
+        let (a, b) = (self.den / g, rhs.den / g);
+        let num = self.num.checked_mul(b)?.checked_sub(rhs.num.checked_mul(a)?)?;
+        Some(Self::new(num, a.checked_mul(rhs.den)?))
+    }
+
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        // cancel first so that the products stay as small as possible
+        let g1 = gcd(self.num, rhs.den).max(1);
+        let g2 = gcd(rhs.num, self.den).max(1);
+        Some(Self::new(
+            (self.num / g1).checked_mul(rhs.num / g2)?,
+            (self.den / g2).checked_mul(rhs.den / g1)?
+        ))
+    }
+}
+
+// t = a (mod m) and t = b (mod n) as a single congruence
+fn combine_congruences((a, m): (i128, i128), (b, n): (i128, i128)) -> Result<Option<(i128, i128)>, SolveError> {
+    let (g, x, _) = extended_gcd(m, n);
+    if (b - a) % g != 0 {
+        return Ok(None);
+    }
+
+    let lcm = (m / g).checked_mul(n).ok_or(SolveError::Overflow)?;
+    let k = ((b - a) / g % (n / g)).checked_mul(x).ok_or(SolveError::Overflow)?.rem_euclid(n / g);
+    let t = m.checked_mul(k).and_then(|mk| mk.checked_add(a)).ok_or(SolveError::Overflow)?;
+    Ok(Some((t.rem_euclid(lcm), lcm)))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SolveError {
+    Unbounded { button: usize },
+    SearchTooLarge,
+    Overflow,
+    MissingCosts { buttons: usize }
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SolveError::Unbounded { button } =>
+                write!(f, "can't find a limit on the presses of button {}", button + 1),
+            SolveError::SearchTooLarge =>
+                write!(f, "more than {SEARCH_LIMIT} combinations of button presses would need to be tried"),
+            SolveError::Overflow =>
+                write!(f, "the numbers involved are too large"),
+            SolveError::MissingCosts { buttons } =>
+                write!(f, "it has {buttons} buttons, but not every one has a cost")
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GeneralMachine {
+    pub buttons: Vec<Vec<i64>>,
+    pub prize: Vec<i64>
+}
+
+// row i gives the presses of button pivots[i] as rhs minus the free
+// buttons' presses times their coefficients
+struct Reduced {
+    matrix: Vec<Vec<Rational>>,
+    pivots: Vec<usize>,
+    free: Vec<usize>
+}
+
+impl Reduced {
+    fn rhs(&self, row: usize) -> Rational {
+        self.matrix[row][self.matrix[row].len() - 1]
+    }
+}
+
+impl GeneralMachine {
+    pub fn min_tokens(&self, costs: &[i64]) -> Result<Option<i64>, SolveError> {
+        let Some(reduced) = self.reduce()? else {
+            return Ok(None);
+        };
+
+        let mut limits = Vec::new();
+        for &j in &reduced.free {
+            match self.press_limit(&reduced, j)? {
+                Some(limit) if limit < 0 => return Ok(None),
+                limit => limits.push(limit)
+            }
+        }
+
+        // the cost is linear in one free button's presses, so that one is
+        // solved directly and only the others are searched
+        let inner = (0..reduced.free.len()).max_by_key(|&i| limits[i].unwrap_or(i128::MAX));
+        let outer: Vec<usize> = (0..reduced.free.len())
+            .filter(|&i| Some(i) != inner)
+            .collect();
+
+        let mut combinations: i128 = 1;
+        for &i in &outer {
+            let limit = limits[i].ok_or(SolveError::Unbounded { button: reduced.free[i] })?;
+            combinations = combinations.saturating_mul(limit + 1);
+        }
+        if combinations > SEARCH_LIMIT {
+            return Err(SolveError::SearchTooLarge);
+        }
+
+        let mut presses = vec![0i128; reduced.free.len()];
+        let mut best: Option<i64> = None;
+        loop {
+            let feasible = match inner {
+                Some(i) => self.best_inner(&reduced, &presses, i, limits[i], costs)?,
+                None => Some(0)
+            };
+
+            if let Some(t) = feasible {
+                if let Some(i) = inner {
+                    presses[i] = t;
+                }
+                if let Some(cost) = self.cost_with_free(&reduced, &presses, costs)? {
+                    best = Some(best.map_or(cost, |b| b.min(cost)));
+                }
+            }
+
+            // advance to the next combination of the other free presses
+            let Some(k) = outer.iter().position(|&i| Some(presses[i]) < limits[i]) else {
+                break;
+            };
+            presses[outer[k]] += 1;
+            for &i in &outer[..k] {
+                presses[i] = 0;
+            }
+        }
+
+        Ok(best)
+    }
+
+    fn reduce(&self) -> Result<Option<Reduced>, SolveError> {
+        let dimensions = self.prize.len();
+        let n = self.buttons.len();
+
+        let mut matrix: Vec<Vec<Rational>> = (0..dimensions)
+            .map(|k| (0..n)
+                .map(|j| Rational::integer(self.buttons[j][k]))
+                .chain(std::iter::once(Rational::integer(self.prize[k])))
+                .collect())
+            .collect();
+
+        let mut pivots = Vec::new();
+        for col in 0..n {
+            let row = pivots.len();
+            let Some(found) = (row..dimensions).find(|&r| matrix[r][col] != Rational::ZERO) else {
+                continue;
+            };
+            matrix.swap(row, found);
+
+            let scale = matrix[row][col].recip();
+            for v in matrix[row].iter_mut() {
+                *v = v.checked_mul(scale).ok_or(SolveError::Overflow)?;
+            }
+
+            for r in (0..dimensions).filter(|&r| r != row) {
+                let factor = matrix[r][col];
+                if factor != Rational::ZERO {
+                    let pivot_row = matrix[row].clone();
+                    for (v, p) in matrix[r].iter_mut().zip(pivot_row) {
+                        *v = factor
+                            .checked_mul(p)
+                            .and_then(|fp| v.checked_sub(fp))
+                            .ok_or(SolveError::Overflow)?;
+                    }
+                }
+            }
+
+            pivots.push(col);
+        }
+
+        // rows without a pivot must read 0 = 0
+        if matrix[pivots.len()..].iter().any(|row| row[n] != Rational::ZERO) {
+            return Ok(None);
+        }
+
+        let free = (0..n)
+            .filter(|col| !pivots.contains(col))
+            .collect();
+
+        Ok(Some(Reduced {
+            matrix,
+            pivots,
+            free
+        }))
+    }
+
+    // an axis every button moves forwards along, or a pivot row with no
+    // negative free coefficients, caps how often button j can be pressed
+    fn press_limit(&self, reduced: &Reduced, j: usize) -> Result<Option<i128>, SolveError> {
+        let from_axes = (0..self.prize.len())
+            .filter(|&k| self.buttons[j][k] > 0 && self.buttons.iter().all(|b| b[k] >= 0))
+            .map(|k| Ok(self.prize[k].div_euclid(self.buttons[j][k]) as i128));
+
+        let from_pivots = (0..reduced.pivots.len())
+            .filter(|&row| reduced.matrix[row][j].num > 0)
+            .filter(|&row| reduced.free.iter().all(|&f| reduced.matrix[row][f].num >= 0))
+            .map(|row| reduced.rhs(row)
+                .checked_mul(reduced.matrix[row][j].recip())
+                .map(Rational::floor)
+                .ok_or(SolveError::Overflow));
+
+        let limits = from_axes
+            .chain(from_pivots)
+            .collect::<Result<Vec<i128>, SolveError>>()?;
+        Ok(limits.into_iter().min())
+    }
+
+    fn best_inner(&self, reduced: &Reduced, presses: &[i128], inner: usize, limit: Option<i128>, costs: &[i64]) -> Result<Option<i128>, SolveError> {
+        let button = reduced.free[inner];
+        let mut lower: i128 = 0;
+        let mut upper = limit;
+        let mut congruence = (0, 1);
+        let mut slope = Rational::integer(costs[button]);
+
+        for (row, &col) in reduced.pivots.iter().enumerate() {
+            // the pivot is pressed a - b * t times
+            let a = pivot_presses(reduced, row, presses, Some(button))?;
+            let b = reduced.matrix[row][button];
+            slope = Rational::integer(costs[col])
+                .checked_mul(b)
+                .and_then(|cb| slope.checked_sub(cb))
+                .ok_or(SolveError::Overflow)?;
+
+            // as integers over a common denominator, (big_a - big_b * t) / d
+            let (big_a, big_b, d) = common_denominator(a, b).ok_or(SolveError::Overflow)?;
+
+            let g = gcd(big_b, d);
+            if big_a % g != 0 {
+                return Ok(None);
+            }
+            let modulus = d / g;
+            if modulus > 1 {
+                let (_, inverse, _) = extended_gcd((big_b / g).rem_euclid(modulus), modulus);
+                let residue = (big_a / g % modulus)
+                    .checked_mul(inverse)
+                    .ok_or(SolveError::Overflow)?
+                    .rem_euclid(modulus);
+                let Some(combined) = combine_congruences(congruence, (residue, modulus))? else {
+                    return Ok(None);
+                };
+                congruence = combined;
+            }
+
+            match big_b.signum() {
+                1 => upper = Some(upper.map_or(big_a.div_euclid(big_b), |u| u.min(big_a.div_euclid(big_b)))),
+                -1 => lower = lower.max(-big_a.div_euclid(-big_b)),
+                _ => if big_a < 0 {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let (residue, modulus) = congruence;
+        let first = lower + (residue - lower).rem_euclid(modulus);
+        if upper.is_some_and(|u| u < first) {
+            return Ok(None);
+        }
+
+        if slope.num < 0 {
+            let upper = upper.ok_or(SolveError::Unbounded { button })?;
+            Ok(Some(upper - (upper - residue).rem_euclid(modulus)))
+        }
+        else {
+            Ok(Some(first))
+        }
+    }
+
+    fn cost_with_free(&self, reduced: &Reduced, presses: &[i128], costs: &[i64]) -> Result<Option<i64>, SolveError> {
+        let mut total: i128 = 0;
+        let mut add = |cost: i64, presses: i128| -> Result<(), SolveError> {
+            total = (cost as i128)
+                .checked_mul(presses)
+                .and_then(|c| total.checked_add(c))
+                .ok_or(SolveError::Overflow)?;
+            Ok(())
+        };
+
+        for (&j, &p) in reduced.free.iter().zip(presses) {
+            add(costs[j], p)?;
+        }
+
+        for (row, &col) in reduced.pivots.iter().enumerate() {
+            match pivot_presses(reduced, row, presses, None)?.as_integer() {
+                Some(value) if value >= 0 => add(costs[col], value)?,
+                _ => return Ok(None)
+            }
+        }
+
+        i64::try_from(total)
+            .map(Some)
+            .map_err(|_| SolveError::Overflow)
+    }
+}
+
+fn common_denominator(a: Rational, b: Rational) -> Option<(i128, i128, i128)> {
+    let d = (a.den / gcd(a.den, b.den)).checked_mul(b.den)?;
+    Some((a.num.checked_mul(d / a.den)?, b.num.checked_mul(d / b.den)?, d))
+}
+
+fn pivot_presses(reduced: &Reduced, row: usize, presses: &[i128], skip: Option<usize>) -> Result<Rational, SolveError> {
+    let mut value = reduced.rhs(row);
+    for (&j, &p) in reduced.free.iter().zip(presses) {
+        if Some(j) != skip {
+            value = reduced.matrix[row][j]
+                .checked_mul(Rational::new(p, 1))
+                .and_then(|cp| value.checked_sub(cp))
+                .ok_or(SolveError::Overflow)?;
+        }
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(buttons: &[&[i64]], prize: &[i64]) -> GeneralMachine {
+        GeneralMachine {
+            buttons: buttons.iter().map(|b| b.to_vec()).collect(),
+            prize: prize.to_vec()
+        }
+    }
+
+    #[test]
+    fn test_two_buttons() {
+        assert_eq!(machine(&[&[94, 34], &[22, 67]], &[8400, 5400]).min_tokens(&[3, 1]), Ok(Some(280)));
+        assert_eq!(machine(&[&[26, 66], &[67, 21]], &[12748, 12176]).min_tokens(&[3, 1]), Ok(None));
+        assert_eq!(machine(&[&[2, 2], &[4, 4]], &[10, 10]).min_tokens(&[3, 1]), Ok(Some(5)));
+    }
+
+    #[test]
+    fn test_three_buttons() {
+        // C moves diagonally and is cheap, so use it as much as possible
+        assert_eq!(machine(&[&[1, 0], &[0, 1], &[1, 1]], &[5, 7]).min_tokens(&[3, 1, 1]), Ok(Some(7)));
+        assert_eq!(machine(&[&[3], &[5], &[7]], &[11]).min_tokens(&[1, 1, 1]), Ok(Some(3)));
+        assert_eq!(machine(&[&[4], &[6]], &[11]).min_tokens(&[1, 1]), Ok(None));
+    }
+
+    #[test]
+    fn test_backwards_button() {
+        // B must be pressed 1500 times, far more than any axis allows for
+        assert_eq!(machine(&[&[1], &[-1]], &[-1500]).min_tokens(&[3, 1]), Ok(Some(1500)));
+        assert_eq!(machine(&[&[1], &[-1]], &[1500]).min_tokens(&[3, 1]), Ok(Some(4500)));
+        // only an odd number of B presses leaves a whole number of A presses
+        assert_eq!(machine(&[&[4], &[6]], &[22]).min_tokens(&[1, 1]), Ok(Some(4)));
+    }
+
+    #[test]
+    fn test_large_prize() {
+        let large = 10_000_000_000_000;
+        let m = machine(&[&[1, 0], &[0, 1], &[1, 1]], &[large, large + 2]);
+        assert_eq!(m.min_tokens(&[3, 1, 1]), Ok(Some(large + 2)));
+    }
+
+    #[test]
+    fn test_large_coefficients() {
+        let buttons = [
+            [999_999_937, 987_654_321, 123_456_789, 555_555_557],
+            [876_543_211, 999_999_929, 234_567_891, 444_444_443],
+            [765_432_109, 345_678_912, 999_999_893, 333_333_331],
+            [654_321_097, 456_789_123, 567_891_234, 999_999_883]
+        ];
+        let presses = [7, 11, 13, 17];
+        let build = |scale: i64| {
+            let buttons: Vec<Vec<i64>> = buttons
+                .iter()
+                .map(|b| b.iter().map(|v| v / scale).collect())
+                .collect();
+            let prize: Vec<i64> = (0..4)
+                .map(|k| (0..4).map(|j| buttons[j][k] * presses[j]).sum())
+                .collect();
+            GeneralMachine { buttons, prize }
+        };
+
+        assert_eq!(build(10_000).min_tokens(&[3, 1, 1, 1]), Ok(Some(62)));
+        // too big for exact arithmetic in an i128, but not a panic
+        assert_eq!(build(1).min_tokens(&[3, 1, 1, 1]), Err(SolveError::Overflow));
+    }
+
+    #[test]
+    fn test_unbounded() {
+        // nothing limits how often B and C are pressed together
+        let m = machine(&[&[1], &[-1], &[-2]], &[5]);
+        assert_eq!(m.min_tokens(&[1, 1, 1]), Err(SolveError::Unbounded { button: 1 }));
+        // every press of B lowers the cost
+        assert_eq!(machine(&[&[1], &[-1]], &[5]).min_tokens(&[1, -2]), Err(SolveError::Unbounded { button: 1 }));
+    }
+
+    #[test]
+    fn test_three_dimensions() {
+        // exactly 3A + 2B + 3C reaches the prize
+        let m = machine(&[&[1, 2, 0], &[0, 1, 3], &[2, 0, 1]], &[9, 8, 9]);
+        assert_eq!(m.min_tokens(&[1, 1, 1]), Ok(Some(8)));
+        assert_eq!(m.min_tokens(&[3, 1, 2]), Ok(Some(17)));
+        assert_eq!(machine(&[&[1, 2, 0], &[0, 1, 3], &[2, 0, 1]], &[7, 8, 13]).min_tokens(&[1, 1, 1]), Ok(None));
+    }
+
+    #[test]
+    fn test_rational() {
+        assert_eq!(Rational::new(2, -4), Rational::new(-1, 2));
+        assert_eq!(Rational::new(3, 4).checked_sub(Rational::new(1, 4)), Some(Rational::new(1, 2)));
+        assert_eq!(Rational::new(3, 4).checked_mul(Rational::integer(4)).and_then(Rational::as_integer), Some(3));
+        assert_eq!(Rational::new(i128::MAX, 1).checked_mul(Rational::integer(2)), None);
+    }
+}
@@ -1,7 +1,11 @@
 use utils::timer::Timer;
 use std::fs::read_to_string;
 use std::path::PathBuf;
-use std::str::FromStr;
+
+mod general;
+mod parse;
+
+use general::{GeneralMachine, SolveError};
 
 #[derive(Debug)]
 struct ClawMachine {
@@ -9,52 +13,143 @@ struct ClawMachine {
     c: [i64; 2]
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct Overflow;
+
+fn checked_mul(a: i64, b: i64) -> Result<i64, Overflow> {
+    a.checked_mul(b).ok_or(Overflow)
+}
+
+fn checked_sub(a: i64, b: i64) -> Result<i64, Overflow> {
+    a.checked_sub(b).ok_or(Overflow)
+}
+
+// returns (g, x, y) with a * x + b * y = g = gcd(a, b)
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    }
+    else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
 impl ClawMachine {
-    fn count_tokens(&self) -> usize {
-        let determinant = self.m[0][0] * self.m[1][1] - self.m[0][1] * self.m[1][0];
-        let inverse = [[self.m[1][1], -self.m[0][1]], [-self.m[1][0], self.m[0][0]]];
+    fn count_tokens(&self, costs: [i64; 2]) -> Result<Option<i64>, Overflow> {
+        let determinant = checked_sub(checked_mul(self.m[0][0], self.m[1][1])?, checked_mul(self.m[0][1], self.m[1][0])?)?;
+        if determinant == 0 {
+            return self.count_tokens_collinear(costs);
+        }
 
-        let x = inverse[0][0] * self.c[0] + inverse[0][1] * self.c[1];
-        let y = inverse[1][0] * self.c[0] + inverse[1][1] * self.c[1];
+        let x = checked_sub(checked_mul(self.m[1][1], self.c[0])?, checked_mul(self.m[0][1], self.c[1])?)?;
+        let y = checked_sub(checked_mul(self.m[0][0], self.c[1])?, checked_mul(self.m[1][0], self.c[0])?)?;
 
         let (x, x_rem) = (x / determinant, x % determinant);
         let (y, y_rem) = (y / determinant, y % determinant);
 
         if x_rem != 0 || y_rem != 0 || x < 0 || y < 0 {
-            return 0;
+            return Ok(None);
         }
         
-        x as usize * 3 + y as usize
+        checked_mul(x, costs[0])?
+            .checked_add(checked_mul(y, costs[1])?)
+            .ok_or(Overflow)
+            .map(Some)
     }
-}
 
-#[derive(Debug)]
-struct ClawMachineParseError;
-
-impl FromStr for ClawMachine {
-    type Err = ClawMachineParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let values = s
-            .lines()
-            .flat_map(|line| line
-                .split(['+', '=', ','])
-                .enumerate()
-                .filter_map(|(idx, s)| if idx == 1 || idx == 3 { Some(s) } else { None })
-            )
-            .map(str::parse)
-            .collect::<Result<Vec<i64>,_>>()
-            .map_err(|_| ClawMachineParseError)?;
-
-        let constant = if cfg!(feature = "part2") {
-            10_000_000_000_000
-        } else {
-            0
+    // A and B move along the same line, so there are either no solutions or
+    // infinitely many along a single axis
+    fn count_tokens_collinear(&self, costs: [i64; 2]) -> Result<Option<i64>, Overflow> {
+        let Some(axis) = (0..2).find(|&axis| self.m[axis] != [0, 0]) else {
+            // neither button moves the claw
+            return Ok((self.c == [0, 0]).then_some(0));
         };
 
-        Ok(Self {
-            m: [ [ values[0], values[2] ], [ values[1], values[3] ] ],
-            c: [ values[4] + constant, values[5] + constant ]
-        })
+        let a = self.m[axis][0] as i128;
+        let b = self.m[axis][1] as i128;
+        let p = self.c[axis] as i128;
+        let (cost_a, cost_b) = (costs[0] as i128, costs[1] as i128);
+
+        let (g, x, y) = extended_gcd(a, b);
+        if p % g != 0 {
+            return Ok(None);
+        }
+
+        // every solution is (i0 + t * u, j0 + t * v) for integer t
+        let (i0, j0) = (x * (p / g), y * (p / g));
+        let (u, v) = (b / g, -a / g);
+
+        // restrict t so that neither button is pressed a negative number of
+        // times
+        let mut lower = None;
+        let mut upper = None;
+        for (start, step) in [(i0, u), (j0, v)] {
+            match step.signum() {
+                1 => {
+                    let bound = -(start.div_euclid(step));
+                    lower = Some(lower.map_or(bound, |l: i128| l.max(bound)));
+                },
+                -1 => {
+                    let bound = start.div_euclid(-step);
+                    upper = Some(upper.map_or(bound, |u: i128| u.min(bound)));
+                },
+                _ => if start < 0 {
+                    return Ok(None);
+                }
+            }
+        }
+
+        if lower.zip(upper).is_some_and(|(l, u)| l > u) {
+            return Ok(None);
+        }
+
+        // the cost changes linearly with t, so the cheapest is at one end
+        let slope = cost_a * u + cost_b * v;
+        let t = match (slope.signum(), lower, upper) {
+            (1 | 0, Some(l), _) => l,
+            (-1 | 0, _, Some(u)) => u,
+            _ => return Ok(None)
+        };
+
+        let (i, j) = (i0 + t * u, j0 + t * v);
+
+        // the prize also has to be on the line the buttons move along
+        let other = 1 - axis;
+        let reached = self.m[other][0] as i128 * i + self.m[other][1] as i128 * j;
+        if reached != self.c[other] as i128 {
+            return Ok(None);
+        }
+
+        i64::try_from(cost_a * i + cost_b * j)
+            .map(Some)
+            .map_err(|_| Overflow)
+    }
+}
+
+impl TryFrom<&GeneralMachine> for ClawMachine {
+    type Error = ();
+    fn try_from(machine: &GeneralMachine) -> Result<Self, Self::Error> {
+        match (machine.buttons.as_slice(), machine.prize.as_slice()) {
+            ([a, b], &[x, y]) => Ok(Self {
+                m: [ [ a[0], b[0] ], [ a[1], b[1] ] ],
+                c: [ x, y ]
+            }),
+            _ => Err(())
+        }
+    }
+}
+
+fn solve(machine: &GeneralMachine, costs: &[i64]) -> Result<Option<i64>, SolveError> {
+    if costs.len() < machine.buttons.len() {
+        return Err(SolveError::MissingCosts { buttons: machine.buttons.len() });
+    }
+
+    match ClawMachine::try_from(machine) {
+        Ok(c) => c
+            .count_tokens([costs[0], costs[1]])
+            .map_err(|Overflow| SolveError::Overflow),
+        Err(()) => machine.min_tokens(costs)
     }
 }
 
@@ -66,13 +161,139 @@ fn main() {
         .expect("Should have file argument")
         .into();
 
-    let total_tokens: usize = read_to_string(&path)
-        .expect("Should be able to read input")
-        .split("\n\n")
-        .map(ClawMachine::from_str)
-        .map(Result::unwrap)
-        .map(|c| c.count_tokens())
+    let mut costs = vec![3, 1];
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--costs" => {
+                let value = args
+                    .next()
+                    .expect("Should have costs after --costs");
+                costs = value
+                    .split(',')
+                    .map(|c| c.parse().expect("Each cost should be a number"))
+                    .collect();
+            },
+            _ => panic!("Unknown option: {arg}")
+        }
+    }
+
+    let input = read_to_string(&path)
+        .expect("Should be able to read input");
+
+    let machines = match parse::parse_machines(&input) {
+        Ok(machines) => machines,
+        Err(e) => {
+            eprintln!("Invalid input: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let total_tokens: i64 = machines
+        .iter()
+        .enumerate()
+        .map(|(i, machine)| match solve(machine, &costs) {
+            Ok(tokens) => tokens.unwrap_or(0),
+            Err(e) => {
+                eprintln!("Can't solve machine {}: {e}", i + 1);
+                std::process::exit(1);
+            }
+        })
         .sum();
-        
+
     println!("Answer: {total_tokens}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(a: [i64; 2], b: [i64; 2], prize: [i64; 2]) -> ClawMachine {
+        ClawMachine {
+            m: [[a[0], b[0]], [a[1], b[1]]],
+            c: prize
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(machine([94, 34], [22, 67], [8400, 5400]).count_tokens([3, 1]), Ok(Some(280)));
+        assert_eq!(machine([26, 66], [67, 21], [12748, 12176]).count_tokens([3, 1]), Ok(None));
+        assert_eq!(machine([17, 86], [84, 37], [7870, 6450]).count_tokens([3, 1]), Ok(Some(200)));
+        assert_eq!(machine([69, 23], [27, 71], [18641, 10279]).count_tokens([3, 1]), Ok(None));
+    }
+
+    #[test]
+    fn test_collinear() {
+        // 2A + 4B = 10 has solutions (5, 0), (3, 1) and (1, 2)
+        assert_eq!(machine([2, 2], [4, 4], [10, 10]).count_tokens([3, 1]), Ok(Some(5)));
+        assert_eq!(machine([2, 2], [4, 4], [10, 10]).count_tokens([1, 3]), Ok(Some(5)));
+        assert_eq!(machine([2, 2], [4, 4], [10, 10]).count_tokens([1, 1]), Ok(Some(3)));
+        assert_eq!(machine([2, 3], [4, 6], [12, 18]).count_tokens([3, 1]), Ok(Some(3)));
+
+        // prize not on the line, or not a multiple of the gcd
+        assert_eq!(machine([2, 2], [4, 4], [10, 12]).count_tokens([3, 1]), Ok(None));
+        assert_eq!(machine([3, 3], [6, 6], [7, 7]).count_tokens([3, 1]), Ok(None));
+
+        // only one button moves the claw
+        assert_eq!(machine([0, 0], [5, 1], [15, 3]).count_tokens([3, 1]), Ok(Some(3)));
+        assert_eq!(machine([0, 0], [0, 0], [0, 0]).count_tokens([3, 1]), Ok(Some(0)));
+        assert_eq!(machine([0, 0], [0, 0], [1, 0]).count_tokens([3, 1]), Ok(None));
+    }
+
+    #[test]
+    fn test_general_agrees() {
+        let example = "\
+Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+69, Y+23
+Button B: X+27, Y+71
+Prize: X=18641, Y=10279
+";
+
+        for general in parse::parse_machines(example).unwrap() {
+            let claw = ClawMachine::try_from(&general).unwrap();
+            assert_eq!(claw.count_tokens([3, 1]), general.min_tokens(&[3, 1]).map_err(|_| Overflow));
+        }
+    }
+
+    #[test]
+    fn test_costs() {
+        assert_eq!(machine([94, 34], [22, 67], [8400, 5400]).count_tokens([1, 1]), Ok(Some(120)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let large = 10_000_000_000_000;
+        assert_eq!(machine([94, 34], [22, 67], [8400 + large, 5400 + large]).count_tokens([3, 1]), Ok(None));
+        assert_eq!(machine([i64::MAX / 2, 3], [5, 7], [10, 10]).count_tokens([3, 1]), Err(Overflow));
+    }
+
+    #[test]
+    fn test_solve_errors() {
+        let [huge, three_buttons]: [GeneralMachine; 2] = parse::parse_machines("\
+Button A: X+4611686018427387903, Y+3
+Button B: X+5, Y+7
+Prize: X=10, Y=10
+
+Button A: X+1, Y+0
+Button B: X+0, Y+1
+Button C: X+1, Y+1
+Prize: X=5, Y=7
+").unwrap().try_into().unwrap();
+
+        assert_eq!(solve(&huge, &[3, 1]), Err(SolveError::Overflow));
+        assert_eq!(solve(&three_buttons, &[3, 1]), Err(SolveError::MissingCosts { buttons: 3 }));
+        assert_eq!(solve(&three_buttons, &[3, 1, 1]), Ok(Some(7)));
+    }
+}
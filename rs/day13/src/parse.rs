@@ -0,0 +1,250 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::general::GeneralMachine;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    ExpectedButton,
+    ExpectedPrize,
+    NoButtons,
+    NoAxes,
+    MalformedField(String),
+    InvalidNumber(String),
+    UnknownAxis(String),
+    DuplicateAxis(String)
+}
+
+// machines and lines count from 1, and lines across the whole input
+#[derive(Debug, Eq, PartialEq)]
+pub struct ParseError {
+    pub machine: usize,
+    pub line: usize,
+    pub kind: ParseErrorKind
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "machine {}, line {}: ", self.machine, self.line)?;
+        match &self.kind {
+            ParseErrorKind::ExpectedButton =>
+                write!(f, "expected a line like \"Button A: X+94, Y+34\""),
+            ParseErrorKind::ExpectedPrize =>
+                write!(f, "expected a line like \"Prize: X=8400, Y=5400\""),
+            ParseErrorKind::NoButtons =>
+                write!(f, "machine has no buttons"),
+            ParseErrorKind::NoAxes =>
+                write!(f, "prize has no coordinates"),
+            ParseErrorKind::MalformedField(field) =>
+                write!(f, "malformed field {field:?}"),
+            ParseErrorKind::InvalidNumber(field) =>
+                write!(f, "invalid number in field {field:?}"),
+            ParseErrorKind::UnknownAxis(axis) =>
+                write!(f, "axis {axis:?} is not one of the prize's axes"),
+            ParseErrorKind::DuplicateAxis(axis) =>
+                write!(f, "axis {axis:?} appears more than once")
+        }
+    }
+}
+
+// a field like `X+94` or `Y = 5400`, ignoring any whitespace
+fn parse_field(field: &str, separators: &[char]) -> Result<(String, i64), ParseErrorKind> {
+    let compact: String = field
+        .split_whitespace()
+        .collect();
+    let malformed = || ParseErrorKind::MalformedField(field.trim().to_string());
+
+    let split = compact
+        .find(separators)
+        .ok_or_else(malformed)?;
+    let (axis, value) = compact.split_at(split);
+    if axis.is_empty() {
+        return Err(malformed());
+    }
+
+    let value = value
+        .strip_prefix('=')
+        .unwrap_or(value);
+    let value = value
+        .strip_prefix('+')
+        .unwrap_or(value)
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidNumber(field.trim().to_string()))?;
+
+    Ok((axis.to_string(), value))
+}
+
+fn strip_label<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let (label, rest) = line.split_once(':')?;
+    let mut words = label.split_whitespace();
+    (words.next() == Some(keyword)).then_some(rest)
+}
+
+fn parse_machine(machine: usize, lines: &[(usize, &str)]) -> Result<GeneralMachine, ParseError> {
+    let error = |line: usize, kind| ParseError { machine, line, kind };
+
+    let (&(prize_line, prize), buttons) = lines
+        .split_last()
+        .ok_or(error(0, ParseErrorKind::NoButtons))?;
+    let prize = strip_label(prize, "Prize")
+        .ok_or(error(prize_line, ParseErrorKind::ExpectedPrize))?;
+
+    let constant = if cfg!(feature = "part2") {
+        10_000_000_000_000
+    } else {
+        0
+    };
+
+    let mut axes: Vec<String> = Vec::new();
+    let mut position = Vec::new();
+    for field in prize.split(',').filter(|f| !f.trim().is_empty()) {
+        let (axis, value) = parse_field(field, &['='])
+            .map_err(|kind| error(prize_line, kind))?;
+        if axes.contains(&axis) {
+            return Err(error(prize_line, ParseErrorKind::DuplicateAxis(axis)));
+        }
+        axes.push(axis);
+        position.push(value + constant);
+    }
+
+    if axes.is_empty() {
+        return Err(error(prize_line, ParseErrorKind::NoAxes));
+    }
+
+    if buttons.is_empty() {
+        return Err(error(prize_line, ParseErrorKind::NoButtons));
+    }
+
+    let buttons = buttons
+        .iter()
+        .map(|&(line, text)| {
+            let movement = strip_label(text, "Button")
+                .ok_or(error(line, ParseErrorKind::ExpectedButton))?;
+
+            let mut button = vec![0; axes.len()];
+            let mut seen = vec![false; axes.len()];
+            for field in movement.split(',').filter(|f| !f.trim().is_empty()) {
+                let (axis, value) = parse_field(field, &['+', '-'])
+                    .map_err(|kind| error(line, kind))?;
+                let idx = axes
+                    .iter()
+                    .position(|a| *a == axis)
+                    .ok_or_else(|| error(line, ParseErrorKind::UnknownAxis(axis.clone())))?;
+                if seen[idx] {
+                    return Err(error(line, ParseErrorKind::DuplicateAxis(axis)));
+                }
+                seen[idx] = true;
+                button[idx] = value;
+            }
+            Ok(button)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(GeneralMachine {
+        buttons,
+        prize: position
+    })
+}
+
+pub fn parse_machines(input: &str) -> Result<Vec<GeneralMachine>, ParseError> {
+    let mut machines = Vec::new();
+    let mut block = Vec::new();
+
+    for (idx, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if !line.is_empty() {
+            block.push((idx + 1, line));
+        }
+        else if !block.is_empty() {
+            machines.push(parse_machine(machines.len() + 1, &block)?);
+            block.clear();
+        }
+    }
+
+    if !block.is_empty() {
+        machines.push(parse_machine(machines.len() + 1, &block)?);
+    }
+
+    Ok(machines)
+}
+
+impl FromStr for GeneralMachine {
+    type Err = ParseError;
+
+    // the prize decides which axes there are; buttons may list them in any
+    // order and leave out those they don't move along
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<(usize, &str)> = s
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+
+        parse_machine(1, &lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(input: &str) -> ParseErrorKind {
+        parse_machines(input).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_parse() {
+        let m: GeneralMachine = "\
+Button A: X+94, Y+34
+Button B: X+22, Y+67
+Button C: Y-3, X+1
+Prize: X=8400, Y=5400
+".parse().unwrap();
+
+        assert_eq!(m.buttons, vec![vec![94, 34], vec![22, 67], vec![1, -3]]);
+        if !cfg!(feature = "part2") {
+            assert_eq!(m.prize, vec![8400, 5400]);
+        }
+
+        let m: GeneralMachine = "Button A: X+1, Z+2\nButton B: Y+3\nPrize: X=1, Y=2, Z=3".parse().unwrap();
+        assert_eq!(m.buttons, vec![vec![1, 0, 2], vec![0, 3, 0]]);
+    }
+
+    #[test]
+    fn test_flexible_layout() {
+        let input = "\r\n  Button A :X + 94,Y+34 \r\nButton B: X+22, Y+67\r\nPrize:X = 8400 , Y=5400\r\n\r\n\r\nButton A: X+26, Y+66\r\nButton B: X+67, Y+21\r\nPrize: X=12748, Y=12176";
+        let machines = parse_machines(input).unwrap();
+
+        assert_eq!(machines.len(), 2);
+        assert_eq!(machines[0].buttons, vec![vec![94, 34], vec![22, 67]]);
+        assert_eq!(machines[1].buttons, vec![vec![26, 66], vec![67, 21]]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = "\
+Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y=21
+Prize: X=12748, Y=12176
+";
+        assert_eq!(parse_machines(input).unwrap_err(), ParseError {
+            machine: 2,
+            line: 6,
+            kind: ParseErrorKind::MalformedField("Y=21".to_string())
+        });
+
+        assert_eq!(kind("Button A: X+1, Y+1\nButton B: X+2, Y+2"), ParseErrorKind::ExpectedPrize);
+        assert_eq!(kind("Button A: X+1, Y+1\nstray\nPrize: X=1, Y=1"), ParseErrorKind::ExpectedButton);
+        assert_eq!(kind("Prize: X=1, Y=1"), ParseErrorKind::NoButtons);
+        assert_eq!(kind("Button A: X+1\nPrize:"), ParseErrorKind::NoAxes);
+        assert_eq!(kind("Button A: X+a\nPrize: X=1"), ParseErrorKind::InvalidNumber("X+a".to_string()));
+        assert_eq!(kind("Button A: W+1\nPrize: X=1"), ParseErrorKind::UnknownAxis("W".to_string()));
+        assert_eq!(kind("Button A: X+1, X+2\nPrize: X=1"), ParseErrorKind::DuplicateAxis("X".to_string()));
+        assert_eq!(kind("Button A: +1\nPrize: X=1"), ParseErrorKind::MalformedField("+1".to_string()));
+    }
+}
@@ -0,0 +1,37 @@
+use std::io;
+use std::path::Path;
+
+// the busiest tile is white and empty tiles are black
+pub fn encode_pgm(width: usize, height: usize, counts: &[usize]) -> Vec<u8> {
+    let max = counts
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut image = format!("P5\n{width} {height}\n255\n").into_bytes();
+    image.extend(counts
+        .iter()
+        .map(|&count| (count * 255 / max) as u8)
+    );
+    image
+}
+
+pub fn write_pgm(path: &Path, width: usize, height: usize, counts: &[usize]) -> io::Result<()> {
+    std::fs::write(path, encode_pgm(width, height, counts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_pgm() {
+        let image = encode_pgm(3, 2, &[0, 1, 2, 4, 0, 0]);
+        assert_eq!(image, b"P5\n3 2\n255\n\x00\x3f\x7f\xff\x00\x00");
+
+        let empty = encode_pgm(2, 1, &[0, 0]);
+        assert_eq!(empty, b"P5\n2 1\n255\n\x00\x00");
+    }
+}
@@ -1,45 +1,191 @@
 use utils::timer::Timer;
 use std::fs::read_to_string;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-const BOARD_WIDTH: usize = 101;
-const BOARD_HEIGHT: usize = 103;
+mod export;
 
+const DEFAULT_ROOM: [usize; 2] = [101, 103];
+
+#[derive(Clone)]
 struct Robot {
     position: [usize; 2],
-    vector: [isize; 2]
+    vector: [isize; 2],
+    room: [usize; 2]
 }
 
 impl Robot {
+    fn in_room(mut self, room: [usize; 2]) -> Self {
+        self.room = room;
+        self
+    }
+
     fn get_quadrant(&self) -> Option<usize> {
-        const HALF_WIDTH: usize = BOARD_WIDTH / 2;
-        const HALF_HEIGHT: usize = BOARD_HEIGHT / 2;
+        let half_width = self.room[0] / 2;
+        let half_height = self.room[1] / 2;
 
-        if self.position[0] == HALF_WIDTH || self.position[1] == HALF_HEIGHT {
+        if self.position[0] == half_width || self.position[1] == half_height {
             return None;
         }
 
-        let qx = self.position[0] / (HALF_WIDTH + 1);
-        let qy = self.position[1] / (HALF_HEIGHT + 1);
+        let qx = self.position[0] / (half_width + 1);
+        let qy = self.position[1] / (half_height + 1);
         
         Some(qy * 2 + qx)
     }
 
     fn make_move(mut self, seconds: usize) -> Self {
-        self.position[0] += (BOARD_WIDTH * seconds).saturating_add_signed(self.vector[0] * seconds as isize);
-        self.position[1] += (BOARD_HEIGHT * seconds).saturating_add_signed(self.vector[1] * seconds as isize);
-        self.position[0] %= BOARD_WIDTH;
-        self.position[1] %= BOARD_HEIGHT;
+        for axis in 0..2 {
+            let size = self.room[axis] as isize;
+            let travelled = (self.vector[axis] % size) * (seconds as isize % size);
+            self.position[axis] = (self.position[axis] as isize + travelled).rem_euclid(size) as usize;
+        }
         self
     }
 
     fn make_move_once(&mut self) {
-        self.position[0] += (BOARD_WIDTH as isize + self.vector[0]) as usize;
-        self.position[1] += (BOARD_HEIGHT as isize + self.vector[1]) as usize;
-        self.position[0] %= BOARD_WIDTH;
-        self.position[1] %= BOARD_HEIGHT;
+        for axis in 0..2 {
+            let size = self.room[axis] as isize;
+            self.position[axis] = (self.position[axis] as isize + self.vector[axis]).rem_euclid(size) as usize;
+        }
+    }
+}
+
+fn safety_factor<'a>(robots: impl Iterator<Item = &'a Robot>) -> usize {
+    robots
+        .fold([0, 0, 0, 0], |mut quadrants, r| {
+            if let Some(q) = r.get_quadrant() {
+                quadrants[q] += 1;
+            }
+            quadrants
+        })
+        .iter()
+        .product()
+}
+
+// the variance times the square of the count, which keeps it an integer
+fn spread(values: impl Iterator<Item = usize>) -> u128 {
+    let (n, sum, sum_squares) = values
+        .map(|v| v as u128)
+        .fold((0u128, 0u128, 0u128), |(n, sum, sum_squares), v| (n + 1, sum + v, sum_squares + v * v));
+
+    n * sum_squares - sum * sum
+}
+
+// the smallest t with t = a (mod m) and t = b (mod n)
+fn chinese_remainder(a: usize, m: usize, b: usize, n: usize) -> Option<usize> {
+    fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+        if b == 0 {
+            (a, 1, 0)
+        }
+        else {
+            let (g, x, y) = extended_gcd(b, a % b);
+            (g, y, x - (a / b) * y)
+        }
+    }
+
+    let (a, m, b, n) = (a as i128, m as i128, b as i128, n as i128);
+    let (g, x, _) = extended_gcd(m, n);
+    if (b - a) % g != 0 {
+        return None;
+    }
+
+    let lcm = m / g * n;
+    let k = ((b - a) / g * x).rem_euclid(n / g);
+    Some((a + m * k).rem_euclid(lcm) as usize)
+}
+
+// motion along each axis repeats with the room's size on that axis, so find
+// when each axis is least spread out and combine the two with the CRT
+fn find_tree(robots: &[Robot]) -> Option<usize> {
+    let room = robots.first()?.room;
+    let mut robots = robots.to_vec();
+    let mut best = [(u128::MAX, 0); 2];
+
+    for seconds in 0..room[0].max(room[1]) {
+        for axis in 0..2 {
+            if seconds < room[axis] {
+                let spread = spread(robots.iter().map(|r| r.position[axis]));
+                if spread < best[axis].0 {
+                    best[axis] = (spread, seconds);
+                }
+            }
+        }
+
+        robots
+            .iter_mut()
+            .for_each(|r| r.make_move_once());
+    }
+
+    chinese_remainder(best[0].1, room[0], best[1].1, room[1])
+}
+
+fn density(robots: &[Robot], [width, height]: [usize; 2]) -> Vec<usize> {
+    let mut grid = vec![0usize; width * height];
+    for r in robots {
+        grid[r.position[1] * width + r.position[0]] += 1;
+    }
+    grid
+}
+
+fn print_robots(robots: &[Robot], room: [usize; 2]) {
+    let [width, height] = room;
+    let grid = density(robots, room);
+
+    for y in 0..height {
+        for x in 0..width {
+            let ch = match grid[y * width + x] {
+                0 => " ",
+                1 => "X",
+                2 => "\x1B[1;31mX\x1B[0m",
+                3..6 => "\x1B[1;33mX\x1B[0m",
+                _ => "\x1B[1;34mX\x1B[0m",
+            };
+
+            print!("{ch}");
+        }
+        println!();
+    }
+}
+
+fn export_frames(robots: &[Robot], room: [usize; 2], seconds: Range<usize>, directory: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    let [width, height] = room;
+
+    let mut robots: Vec<Robot> = robots
+        .iter()
+        .cloned()
+        .map(|r| r.make_move(seconds.start))
+        .collect();
+
+    for second in seconds {
+        let path = directory.join(format!("frame_{second:06}.pgm"));
+        export::write_pgm(&path, width, height, &density(&robots, room))?;
+
+        robots
+            .iter_mut()
+            .for_each(|r| r.make_move_once());
     }
+
+    Ok(())
+}
+
+// `A..B` or `A..=B`
+fn parse_range(s: &str) -> Option<Range<usize>> {
+    let (start, end) = s.split_once("..")?;
+    let start = start.parse().ok()?;
+    match end.strip_prefix('=') {
+        Some(end) => Some(start..end.parse::<usize>().ok()? + 1),
+        None => Some(start..end.parse().ok()?)
+    }
+}
+
+// `WIDTHxHEIGHT`, neither of which can be 0
+fn parse_size(s: &str) -> Option<[usize; 2]> {
+    let (width, height) = s.split_once('x')?;
+    let size = [width.parse().ok()?, height.parse().ok()?];
+    size.iter().all(|&n| n > 0).then_some(size)
 }
 
 #[derive(Debug)]
@@ -58,7 +204,8 @@ impl FromStr for Robot {
 
         Ok(Self {
             position: [values[0] as usize, values[1] as usize],
-            vector: [values[2], values[3]]
+            vector: [values[2], values[3]],
+            room: DEFAULT_ROOM
         })
     }
 }
@@ -74,77 +221,166 @@ fn main() {
     let input = read_to_string(path)
         .expect("Should be able to read from input");
 
-    let robots = input
+    let mut room = DEFAULT_ROOM;
+    let mut export: Option<PathBuf> = None;
+    let mut frames = 0..100;
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => room = args
+                .next()
+                .as_deref()
+                .and_then(parse_size)
+                .expect("Size should be written as WIDTHxHEIGHT, with neither 0"),
+            "--export" => export = Some(args
+                .next()
+                .expect("Should have a directory after --export")
+                .into()),
+            "--frames" => frames = args
+                .next()
+                .as_deref()
+                .and_then(parse_range)
+                .expect("Frames should be a range of seconds like 0..100"),
+            _ => panic!("Unknown option: {arg}")
+        }
+    }
+
+    let robots: Vec<Robot> = input
         .lines()
         .map(Robot::from_str)
-        .map(Result::unwrap);
+        .map(|r| r.unwrap().in_room(room))
+        .collect();
+
+    if let Some(directory) = export {
+        export_frames(&robots, room, frames, &directory)
+            .expect("Should be able to write frames");
+    }
 
     let answer = if !cfg!(feature = "part2") {
-        robots
+        let robots: Vec<Robot> = robots
+            .into_iter()
             .map(|r| r.make_move(100))
-            .fold([0, 0, 0, 0], |mut quadrants, r| {
-                if let Some(q) = r.get_quadrant() {
-                    quadrants[q] += 1;
-                }
-                quadrants
-            })
-            .iter()
-            .product()
+            .collect();
+        safety_factor(robots.iter())
     }
     else {
-        let mut robots: Vec<Robot> = robots.collect();
-        let mut min = (usize::MAX, 0);
-
-        // after BOARD_WIDTH * BOARD_HEIGHT iterations, the board should
-        // "reset" itself (I think), so that should be the upper bound
-        for seconds in 1..=(BOARD_WIDTH * BOARD_HEIGHT) {
-            robots
-                .iter_mut()
-                .for_each(|r| r.make_move_once());
-            
-            let safety_score: usize = robots
-                .iter()
-                .fold([0, 0, 0, 0], |mut quadrants, r| {
-                    if let Some(q) = r.get_quadrant() {
-                        quadrants[q] += 1;
-                    }
-                    quadrants
-                })
-                .iter()
-                .product();
-
-            if safety_score < min.0 {
-                min.0 = safety_score;
-                min.1 = seconds;
-
-                if cfg!(feature = "print_tree") {
-                    let mut grid = [0usize; BOARD_WIDTH * BOARD_HEIGHT];
-                    for r in &robots {
-                        grid[r.position[1] * BOARD_WIDTH + r.position[0]] += 1;
-                    }
-
-                    for y in 0..BOARD_HEIGHT {
-                        for x in 0..BOARD_WIDTH {
-                            let ch = match grid[y * BOARD_WIDTH + x] {
-                                0 => " ",
-                                1 => "X",
-                                2 => "\x1B[1;31mX\x1B[0m",
-                                3..6 => "\x1B[1;33mX\x1B[0m",
-                                _ => "\x1B[1;34mX\x1B[0m",
-                            };
-
-                            print!("{ch}");
-                        }
-                        println!();
-                    }
-                    println!("---------------------------------------");
-                }
-            }
+        let seconds = find_tree(&robots).expect("Should be a tree!");
+
+        if cfg!(feature = "print_tree") {
+            let robots: Vec<Robot> = robots
+                .into_iter()
+                .map(|r| r.make_move(seconds))
+                .collect();
+            print_robots(&robots, room);
         }
 
-        assert!(min.1 > 0, "Should be a minimum!");
-        min.1
+        seconds
     };
 
     println!("Answer: {answer}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3
+";
+
+    #[test]
+    fn test_example() {
+        let robots: Vec<Robot> = EXAMPLE
+            .lines()
+            .map(|l| l.parse::<Robot>().unwrap().in_room([11, 7]).make_move(100))
+            .collect();
+
+        assert_eq!(safety_factor(robots.iter()), 12);
+    }
+
+    #[test]
+    fn test_make_move() {
+        let mut once = "p=2,4 v=2,-3".parse::<Robot>().unwrap().in_room([11, 7]);
+        for seconds in 1..=5 {
+            once.make_move_once();
+            let jumped = "p=2,4 v=2,-3".parse::<Robot>().unwrap().in_room([11, 7]).make_move(seconds);
+            assert_eq!(once.position, jumped.position);
+        }
+        assert_eq!(once.position, [1, 3]);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("0..100"), Some(0..100));
+        assert_eq!(parse_range("5..=7"), Some(5..8));
+        assert_eq!(parse_range("5-7"), None);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("11x7"), Some([11, 7]));
+        assert_eq!(parse_size("0x7"), None);
+        assert_eq!(parse_size("11x0"), None);
+        assert_eq!(parse_size("11,7"), None);
+    }
+
+    #[test]
+    fn test_density() {
+        let robots: Vec<Robot> = EXAMPLE
+            .lines()
+            .map(|l| l.parse::<Robot>().unwrap().in_room([11, 7]))
+            .collect();
+
+        let grid = density(&robots, [11, 7]);
+        assert_eq!(grid.iter().sum::<usize>(), 12);
+        assert_eq!(grid[3], 2);
+
+        assert_eq!(density(&[], [3, 2]), vec![0; 6]);
+    }
+
+    #[test]
+    fn test_chinese_remainder() {
+        assert_eq!(chinese_remainder(2, 3, 3, 5), Some(8));
+        assert_eq!(chinese_remainder(0, 101, 0, 103), Some(0));
+        assert_eq!(chinese_remainder(1, 4, 2, 6), None);
+        assert_eq!(chinese_remainder(1, 4, 3, 6), Some(9));
+    }
+
+    #[test]
+    fn test_find_tree() {
+        // robots that all meet in a small cluster at a known time, having
+        // started from positions worked backwards from there
+        let room = [101, 103];
+        let target = 6512;
+        let mut state: u64 = 0x1414_1414;
+        let mut next = |limit: usize| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as usize % limit
+        };
+
+        let robots: Vec<Robot> = (0..300)
+            .map(|_| {
+                let vector = [next(201) as isize - 100, next(201) as isize - 100];
+                let cluster = [40 + next(10), 50 + next(10)];
+                let position = [0, 1].map(|axis| {
+                    let size = room[axis] as isize;
+                    (cluster[axis] as isize - vector[axis] * target).rem_euclid(size) as usize
+                });
+                Robot { position, vector, room }
+            })
+            .collect();
+
+        assert_eq!(find_tree(&robots), Some(target as usize));
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::board::Board;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Key {
+	Move(u8),
+	Quit,
+	Other
+}
+
+// arrow key escape sequences as well as `<>^v` typed directly
+pub fn decode_keys(bytes: impl IntoIterator<Item = u8>) -> impl Iterator<Item = Key> {
+	let mut bytes = bytes.into_iter();
+	std::iter::from_fn(move || {
+		let key = match bytes.next()? {
+			b @ (b'<' | b'>' | b'^' | b'v') => Key::Move(b),
+			b'q' | 0x03 | 0x04 => Key::Quit,
+			0x1b => {
+				if bytes.next() != Some(b'[') {
+					return Some(Key::Other);
+				}
+				match bytes.next() {
+					Some(b'A') => Key::Move(b'^'),
+					Some(b'B') => Key::Move(b'v'),
+					Some(b'C') => Key::Move(b'>'),
+					Some(b'D') => Key::Move(b'<'),
+					_ => Key::Other
+				}
+			},
+			_ => Key::Other
+		};
+		Some(key)
+	})
+}
+
+// signals are off too, so that Ctrl-C arrives as a key and quits normally,
+// restoring the terminal on the way out
+struct RawMode {
+	saved: String
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+	let output = Command::new("stty")
+		.args(args)
+		.stdin(Stdio::inherit())
+		.output()?;
+
+	if !output.status.success() {
+		return Err(io::Error::other("stty failed"));
+	}
+
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl RawMode {
+	fn enable() -> io::Result<Self> {
+		let saved = stty(&["-g"])?;
+		stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+		Ok(Self {
+			saved
+		})
+	}
+}
+
+impl Drop for RawMode {
+	fn drop(&mut self) {
+		let _ = stty(&[&self.saved]);
+	}
+}
+
+fn draw<'a, T: Board<'a> + Display>(board: &T, moves: &[u8], output: &mut impl Write) -> io::Result<()> {
+	write!(output, "\x1b[2J\x1b[H")?;
+	let frame = board.to_string();
+	writeln!(output, "{}", frame.trim_end())?;
+	writeln!(output, "Moves: {}  GPS sum: {}", moves.len(), board.sum_gps())?;
+	writeln!(output, "Arrow keys or <>^v to move, q to quit")?;
+	output.flush()
+}
+
+pub fn session<'a, T: Board<'a> + Display>(board: &mut T, input: impl BufRead, output: &mut impl Write) -> io::Result<Vec<u8>> {
+	let mut moves = Vec::new();
+	draw(board, &moves, output)?;
+
+	let bytes = input
+		.bytes()
+		.map_while(Result::ok);
+
+	for key in decode_keys(bytes) {
+		match key {
+			Key::Move(instruction) => {
+				board.process_instruction(instruction);
+				moves.push(instruction);
+				draw(board, &moves, output)?;
+			},
+			Key::Quit => break,
+			Key::Other => ()
+		}
+	}
+
+	Ok(moves)
+}
+
+pub fn format_instructions(map: &[u8], moves: &[u8]) -> Vec<u8> {
+	let mut result = map.trim_ascii_end().to_vec();
+	result.extend_from_slice(b"\n\n");
+	for line in moves.chunks(70) {
+		result.extend_from_slice(line);
+		result.push(b'\n');
+	}
+	result
+}
+
+pub fn run<'a, T: Board<'a> + Display>(map: &'a [u8], save_path: Option<&Path>) -> io::Result<()> {
+	let mut board = T::from(map);
+
+	let moves = {
+		// not being attached to a terminal is fine, input is just buffered
+		let _raw_mode = RawMode::enable().ok();
+		session(&mut board, io::stdin().lock(), &mut io::stdout().lock())?
+	};
+
+	if let Some(path) = save_path {
+		std::fs::write(path, format_instructions(map, &moves))?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::warehouse::Part1Board;
+
+	#[test]
+	fn test_decode_keys() {
+		let keys: Vec<Key> = decode_keys(b"<\x1b[A\x1b[Bx\x1b[C\x1b[Dvq".iter().copied()).collect();
+		assert_eq!(keys, vec![
+			Key::Move(b'<'),
+			Key::Move(b'^'),
+			Key::Move(b'v'),
+			Key::Other,
+			Key::Move(b'>'),
+			Key::Move(b'<'),
+			Key::Move(b'v'),
+			Key::Quit
+		]);
+	}
+
+	#[test]
+	fn test_session() {
+		let map = b"########\n#..O.O.#\n##@.O..#\n#...O..#\n#.#.O..#\n#...O..#\n#......#\n########";
+		let mut board = Part1Board::from(map.as_slice());
+		let mut output = Vec::new();
+
+		let moves = session(&mut board, b"<^^>>>vv<v>>v<<q>>".as_slice(), &mut output).unwrap();
+
+		assert_eq!(moves, b"<^^>>>vv<v>>v<<");
+		assert_eq!(board.sum_gps(), 2028);
+		assert!(String::from_utf8(output).unwrap().ends_with("Moves: 15  GPS sum: 2028\nArrow keys or <>^v to move, q to quit\n"));
+
+		let saved = format_instructions(map, &moves);
+		assert!(saved.ends_with(b"########\n\n<^^>>>vv<v>>v<<\n"));
+	}
+}
@@ -1,23 +1,30 @@
 use utils::timer::Timer;
+use std::fmt::Display;
 use std::fs::read;
+use std::io::BufRead;
 use std::path::PathBuf;
 
 mod board;
-mod part1board;
-mod part2board;
+mod interactive;
+mod replay;
+mod validate;
+mod warehouse;
 
 use board::Board;
-use part1board::Part1Board;
-use part2board::Part2Board;
+use replay::Replay;
+use warehouse::{Part1Board, Part2Board};
 
-fn get_answer<'a, T: Board<'a>>(input: &'a [u8]) -> usize {
-    let split_point = input
-        .windows(2)
-        .position(|ch| ch[0] == b'\n' && ch[1] == b'\n')
-        .expect("Should have double line break in input");
+// input without a blank line is a map with no instructions
+fn split_input(input: &[u8]) -> (&[u8], &[u8]) {
+    match input.windows(2).position(|ch| ch[0] == b'\n' && ch[1] == b'\n') {
+        Some(split_point) => (&input[..split_point], &input[split_point+1..]),
+        None => (input.trim_ascii_end(), &[])
+    }
+}
 
-    let mut board: T = input[..split_point].into();
-    let instructions_data = &input[split_point+1..];
+fn get_answer<'a, T: Board<'a>>(input: &'a [u8]) -> usize {
+    let (map, instructions_data) = split_input(input);
+    let mut board: T = map.into();
 
     for i in instructions_data {
         board.process_instruction(*i);
@@ -26,6 +33,52 @@ fn get_answer<'a, T: Board<'a>>(input: &'a [u8]) -> usize {
     board.sum_gps()
 }
 
+fn print_frame<'a, T: Board<'a> + Clone + Display>(replay: &Replay<T>) {
+    let last_move = replay
+        .last_move()
+        .map(|m| m as char)
+        .unwrap_or('-');
+
+    println!("Move {}/{} ({last_move}), GPS sum {}", replay.position(), replay.len(), replay.current().sum_gps());
+    print!("{}", replay.current());
+}
+
+// an empty line or `n` steps forward, `b` or `u` undoes, `g N` jumps to move N and `q` quits
+fn run_replay<'a, T: Board<'a> + Clone + Display>(input: &'a [u8]) {
+    let (map, instructions_data) = split_input(input);
+    let mut replay = Replay::record(T::from(map), instructions_data);
+    print_frame(&replay);
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("Should be able to read from stdin");
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None | Some("n" | "next") => {
+                replay.step_forward();
+            },
+            Some("b" | "back" | "u" | "undo") => {
+                replay.step_back();
+            },
+            Some("g" | "goto") => match words.next().and_then(|n| n.parse().ok()) {
+                Some(n) => {
+                    replay.jump_to(n);
+                },
+                None => {
+                    eprintln!("Expected a move number");
+                    continue;
+                }
+            },
+            Some("q" | "quit") => break,
+            Some(other) => {
+                eprintln!("Unknown command: {other}");
+                continue;
+            }
+        }
+
+        print_frame(&replay);
+    }
+}
+
 fn main() {
     let _timer = Timer::new();
     
@@ -37,6 +90,49 @@ fn main() {
     let input = read(path)
         .expect("Should be able to read from input");
 
+    let (map, _) = split_input(&input);
+    if let Err(e) = validate::validate_map(map) {
+        eprintln!("Invalid map: {e}");
+        std::process::exit(1);
+    }
+
+    let mut replay = false;
+    let mut interactive = false;
+    let mut save_path: Option<PathBuf> = None;
+    let mut args = std::env::args_os().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--replay") => replay = true,
+            Some("--interactive") => interactive = true,
+            Some("--save") => save_path = Some(args
+                .next()
+                .expect("Should have a filename after --save")
+                .into()),
+            _ => panic!("Unknown option: {}", arg.to_string_lossy())
+        }
+    }
+
+    if interactive {
+        let result = if cfg!(feature = "part2") {
+            interactive::run::<Part2Board>(map, save_path.as_deref())
+        }
+        else {
+            interactive::run::<Part1Board>(map, save_path.as_deref())
+        };
+        result.expect("Interactive session failed");
+        return;
+    }
+
+    if replay {
+        if cfg!(feature = "part2") {
+            run_replay::<Part2Board>(&input);
+        }
+        else {
+            run_replay::<Part1Board>(&input);
+        }
+        return;
+    }
+
     let answer = if cfg!(feature = "part2") {
         get_answer::<Part2Board>(&input)
     }
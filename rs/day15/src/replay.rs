@@ -0,0 +1,168 @@
+use crate::board::InstructionReader;
+
+// any other frame is rebuilt from the snapshot before it
+const KEYFRAME_INTERVAL: usize = 64;
+
+pub struct Replay<T> {
+	keyframes: Vec<T>,
+	moves: Vec<u8>,
+	position: usize,
+	current: T
+}
+
+impl<T: InstructionReader + Clone> Replay<T> {
+	pub fn record(initial: T, instructions: &[u8]) -> Self {
+		let moves: Vec<u8> = instructions
+			.iter()
+			.copied()
+			.filter(|ch| matches!(ch, b'<' | b'>' | b'^' | b'v'))
+			.collect();
+
+		let mut keyframes = vec![initial.clone()];
+		let mut board = initial.clone();
+		for (i, &m) in moves.iter().enumerate() {
+			board.process_instruction(m);
+			if (i + 1) % KEYFRAME_INTERVAL == 0 {
+				keyframes.push(board.clone());
+			}
+		}
+
+		Self {
+			keyframes,
+			moves,
+			position: 0,
+			current: initial
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.moves.len()
+	}
+
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	pub fn current(&self) -> &T {
+		&self.current
+	}
+
+	pub fn last_move(&self) -> Option<u8> {
+		self.position
+			.checked_sub(1)
+			.map(|i| self.moves[i])
+	}
+
+	// clamped to the end of the recording
+	pub fn jump_to(&mut self, n: usize) -> &T {
+		let n = n.min(self.moves.len());
+
+		if n < self.position || n - self.position > KEYFRAME_INTERVAL {
+			let keyframe = n / KEYFRAME_INTERVAL;
+			self.current = self.keyframes[keyframe].clone();
+			self.position = keyframe * KEYFRAME_INTERVAL;
+		}
+
+		while self.position < n {
+			self.current.process_instruction(self.moves[self.position]);
+			self.position += 1;
+		}
+
+		&self.current
+	}
+
+	pub fn step_forward(&mut self) -> Option<&T> {
+		if self.position == self.moves.len() {
+			return None;
+		}
+		Some(self.jump_to(self.position + 1))
+	}
+
+	pub fn step_back(&mut self) -> Option<&T> {
+		if self.position == 0 {
+			return None;
+		}
+		Some(self.jump_to(self.position - 1))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::board::Board;
+	use crate::warehouse::{Part1Board, Part2Board};
+
+	const MAP: &[u8] = b"\
+##########
+#..O..O.O#
+#......O.#
+#.OO..O.O#
+#..O@..O.#
+#O#..O...#
+#O..O..O.#
+#.OO.O.OO#
+#....O...#
+##########
+";
+
+	const MOVES: &[u8] = b"\
+<vv>^<v^>v>^vv^v>v<>v^v<v<^vv<<<^><<><>>v<vvv<>^v^>^<<<><<v<<<v^vv^v>^
+vvv<<^>^v^^><<>>><>^<<><^vv^^<>vvv<>><^^v>^>vv<>v<<<<v<^v>^<^^>>>^<v<v
+><>vv>v^v^<>><>>>><^^>vv>v<^^^>>v^v^<^^>v^^>v^<^v>v<>>v^v^<v>v^^<^^vv<
+<<v<^>>^^^^>>>v^<>vvv^><v<<<>^^^vv^<vvv>^>v<^^^^v<>^>vvvv><>>v^<<^^^^^
+^><^><>>><>^^<<^^v>>><^<v>^<vv>>v>>>^v><>^v><<<<v>>v<v<v>vvv>^<><<>^><
+^>><>^v<><^vvv<^^<><v<<<<<><^v<<<><<<^^<v<^^^><^>>^<v^><<<^>>^v<v^v<v^
+>^>>^v>vv>^<<^v<>><<><<v<<v><>v<^vv<<<>^^v^>^^>>><<^v>>v^v><^^>>^<>vv^
+<><^^>^^^<><vvvvv^v<v<<>^v<v>v<<^><<><<><<<^^<<<^<<>><<><^^^>^^<>^>v<>
+^^>vv<^v^v<vv>^<><v<^v>^^^>>>^^vvv^>vvv<>>>^<^>>>>>^<<^v>^vvv<>^<><<v>
+v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^
+";
+
+	fn check_against_direct<'a, T: Board<'a> + Clone + std::fmt::Display>(map: &'a [u8]) {
+		let mut replay = Replay::record(T::from(map), MOVES);
+		assert_eq!(replay.len(), 700);
+
+		let mut direct = T::from(map);
+		let frames: Vec<String> = std::iter::once(direct.to_string())
+			.chain(replay.moves.clone().into_iter().map(|m| {
+				direct.process_instruction(m);
+				direct.to_string()
+			}))
+			.collect();
+
+		for n in [0, 1, 63, 64, 65, 300, 699, 700] {
+			assert_eq!(replay.jump_to(n).to_string(), frames[n]);
+		}
+
+		replay.jump_to(130);
+		for n in (0..130).rev() {
+			assert_eq!(replay.step_back().unwrap().to_string(), frames[n]);
+		}
+		assert!(replay.step_back().is_none());
+
+		for frame in frames.iter().skip(1) {
+			assert_eq!(&replay.step_forward().unwrap().to_string(), frame);
+		}
+		assert!(replay.step_forward().is_none());
+		assert_eq!(replay.position(), 700);
+	}
+
+	#[test]
+	fn test_replay_part1() {
+		check_against_direct::<Part1Board>(MAP);
+
+		let mut replay = Replay::record(Part1Board::from(MAP), MOVES);
+		replay.jump_to(700);
+		assert_eq!(replay.current().sum_gps(), 10092);
+		assert_eq!(replay.last_move(), Some(b'^'));
+	}
+
+	#[test]
+	fn test_replay_part2() {
+		check_against_direct::<Part2Board>(MAP);
+
+		let mut replay = Replay::record(Part2Board::from(MAP), MOVES);
+		replay.jump_to(700);
+		assert_eq!(replay.current().sum_gps(), 9021);
+	}
+}
@@ -0,0 +1,132 @@
+use std::fmt::Display;
+
+// lines and columns count from 1
+#[derive(Debug, Eq, PartialEq)]
+pub enum MapError {
+	UnknownCharacter { line: usize, column: usize, ch: char },
+	NoRobot,
+	MultipleRobots { line: usize, column: usize, first: (usize, usize) },
+	RobotOutsideWall { line: usize, column: usize },
+	RaggedRow { line: usize, expected: usize, found: usize }
+}
+
+impl Display for MapError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			MapError::UnknownCharacter { line, column, ch } =>
+				write!(f, "line {line}, column {column}: unknown character {ch:?}"),
+			MapError::NoRobot =>
+				write!(f, "there is no robot on the map"),
+			MapError::MultipleRobots { line, column, first: (first_line, first_column) } =>
+				write!(f, "line {line}, column {column}: second robot (the first is at line {first_line}, column {first_column})"),
+			MapError::RobotOutsideWall { line, column } =>
+				write!(f, "line {line}, column {column}: robot is not enclosed by walls"),
+			MapError::RaggedRow { line, expected, found } =>
+				write!(f, "line {line}: expected {expected} columns but found {found}")
+		}
+	}
+}
+
+pub fn validate_map(map: &[u8]) -> Result<(), MapError> {
+	let rows: Vec<&[u8]> = map
+		.strip_suffix(b"\n")
+		.unwrap_or(map)
+		.split(|ch| *ch == b'\n')
+		.collect();
+
+	let width = rows[0].len();
+	let mut robot = None;
+
+	for (y, row) in rows.iter().enumerate() {
+		if row.len() != width {
+			return Err(MapError::RaggedRow { line: y + 1, expected: width, found: row.len() });
+		}
+
+		for (x, &ch) in row.iter().enumerate() {
+			match ch {
+				b'#' | b'.' | b'O' => (),
+				b'@' => {
+					if let Some((first_x, first_y)) = robot {
+						return Err(MapError::MultipleRobots {
+							line: y + 1,
+							column: x + 1,
+							first: (first_y + 1, first_x + 1)
+						});
+					}
+					robot = Some((x, y));
+				},
+				_ => return Err(MapError::UnknownCharacter { line: y + 1, column: x + 1, ch: ch as char })
+			}
+		}
+	}
+
+	let (robot_x, robot_y) = robot.ok_or(MapError::NoRobot)?;
+
+	// flood fill everywhere the robot could possibly reach, treating boxes as
+	// movable, and make sure none of it is on the edge of the map
+	let height = rows.len();
+	let mut seen = vec![false; width * height];
+	let mut stack = vec![(robot_x, robot_y)];
+	while let Some((x, y)) = stack.pop() {
+		if rows[y][x] == b'#' || seen[y * width + x] {
+			continue;
+		}
+		seen[y * width + x] = true;
+
+		if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+			return Err(MapError::RobotOutsideWall { line: robot_y + 1, column: robot_x + 1 });
+		}
+
+		stack.extend([(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]);
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_valid() {
+		assert_eq!(validate_map(b"#####\n#.O@#\n#####\n"), Ok(()));
+		assert_eq!(validate_map(b"#####\n#.O@#\n#####"), Ok(()));
+	}
+
+	#[test]
+	fn test_unknown_character() {
+		assert_eq!(
+			validate_map(b"#####\n#.x@#\n#####\n"),
+			Err(MapError::UnknownCharacter { line: 2, column: 3, ch: 'x' })
+		);
+	}
+
+	#[test]
+	fn test_robots() {
+		assert_eq!(validate_map(b"#####\n#.O.#\n#####\n"), Err(MapError::NoRobot));
+		assert_eq!(
+			validate_map(b"#####\n#@..#\n#..@#\n#####\n"),
+			Err(MapError::MultipleRobots { line: 3, column: 4, first: (2, 2) })
+		);
+	}
+
+	#[test]
+	fn test_robot_outside_wall() {
+		assert_eq!(
+			validate_map(b"#####\n#...#\n##.##\n#.@.#\n"),
+			Err(MapError::RobotOutsideWall { line: 4, column: 3 })
+		);
+		assert_eq!(
+			validate_map(b"......\n.####.\n.#..#.\n.####.\n.@....\n"),
+			Err(MapError::RobotOutsideWall { line: 5, column: 2 })
+		);
+	}
+
+	#[test]
+	fn test_ragged_row() {
+		assert_eq!(
+			validate_map(b"#####\n#.@#\n#####\n"),
+			Err(MapError::RaggedRow { line: 2, expected: 5, found: 4 })
+		);
+	}
+}
@@ -0,0 +1,368 @@
+use std::fmt::Display;
+use std::collections::BTreeSet;
+use crate::board::{Board, InstructionProcessor};
+
+#[derive(Eq,PartialEq,Clone,Copy)]
+pub enum Cell {
+	Empty,
+	Wall,
+	Box { part: usize },
+	// where the line breaks were; blocks like a wall but isn't drawn
+	Newline
+}
+
+// every map tile, and so every box, is SCALE cells wide
+#[derive(Clone)]
+pub struct Warehouse<const SCALE: usize> {
+	cells: Vec<Cell>,
+	width: usize,
+	robot: usize,
+	walls: usize,
+}
+
+pub type Part1Board = Warehouse<1>;
+pub type Part2Board = Warehouse<2>;
+
+impl<const SCALE: usize> Display for Warehouse<SCALE> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let mut x = 0;
+		for c in &self.cells {
+			if x == self.robot {
+				write!(f, "@")?;
+			}
+			else {
+				write!(f, "{}", match c {
+					Cell::Empty => ".",
+					Cell::Wall => "#",
+					Cell::Box { .. } if SCALE == 1 => "O",
+					Cell::Box { part: 0 } => "[",
+					Cell::Box { part } if *part == SCALE - 1 => "]",
+					Cell::Box { .. } => "=",
+					Cell::Newline => ""
+				})?;
+			}
+			x += 1;
+			if x % self.width == 0 {
+				writeln!(f)?;
+			}
+		}
+		if x % self.width != 0 {
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+impl<const SCALE: usize> From<&[u8]> for Warehouse<SCALE> {
+	fn from(value: &[u8]) -> Self {
+		let width = value
+			.iter()
+			.position(|ch| *ch == b'\n')
+			.map(|w| (w + 1) * SCALE)
+			.unwrap_or(value.len() * SCALE);
+
+		let robot = value
+			.iter()
+			.position(|ch| *ch == b'@')
+			.map(|r| r * SCALE)
+			.expect("There should be a robot on this map");
+
+		let cells = value
+			.iter()
+			.flat_map(|ch| (0..SCALE).map(move |part| match ch {
+				b'#' => Cell::Wall,
+				b'O' => Cell::Box { part },
+				b'\n' => Cell::Newline,
+				_ => Cell::Empty,
+			}))
+			.collect::<Vec<Cell>>();
+
+		let walls = cells
+			.iter()
+			.filter(|cell| **cell == Cell::Wall)
+			.count();
+
+		Self {
+			cells,
+			width,
+			robot,
+			walls
+		}
+	}
+}
+
+impl<const SCALE: usize> Board<'_> for Warehouse<SCALE> {
+	fn sum_gps(&self) -> usize {
+		self.cells
+			.iter()
+			.enumerate()
+			.filter(|(_idx, cell)| **cell == Cell::Box { part: 0 })
+			.map(|(idx, _cell)| idx / self.width * 100 + idx % self.width)
+			.sum()
+	}
+}
+
+impl<const SCALE: usize> Warehouse<SCALE> {
+	// the left edge of every box pushed, or the wall that blocks the push
+	fn find_boxes(&self, position: usize, offset: isize, boxes: &mut BTreeSet<usize>) -> Result<(),usize> {
+		match self.cells[position] {
+			Cell::Box { part } => {
+				let start = position - part;
+				if !boxes.insert(start) {
+					return Ok(());
+				}
+
+				let this_box = start..start + SCALE;
+				for cell in this_box.clone() {
+					let next = (cell as isize + offset) as usize;
+					if !this_box.contains(&next) {
+						self.find_boxes(next, offset, boxes)?;
+					}
+				}
+			},
+			Cell::Wall | Cell::Newline => {
+				return Err(position);
+			},
+			Cell::Empty => ()
+		}
+		Ok(())
+	}
+
+	fn relative_move(&mut self, offset: isize) {
+		let next = (self.robot as isize + offset) as usize;
+		let mut boxes = BTreeSet::new();
+		if let Err(_wall) = self.find_boxes(next, offset, &mut boxes) {
+			return;
+		}
+
+		// lift every box before putting them down again, so that boxes being
+		// pushed into each other's old cells don't need ordering
+		for &start in &boxes {
+			self.cells[start..start + SCALE].fill(Cell::Empty);
+		}
+		for &start in &boxes {
+			let target = (start as isize + offset) as usize;
+			for part in 0..SCALE {
+				self.cells[target + part] = Cell::Box { part };
+			}
+		}
+
+		self.robot = next;
+
+		if cfg!(debug_assertions) {
+			if let Err(e) = self.check_invariants() {
+				panic!("Warehouse is in an illegal state after a move: {e}\n{self}");
+			}
+		}
+	}
+
+	fn location(&self, position: usize) -> (usize, usize) {
+		(position / self.width + 1, position % self.width / SCALE + 1)
+	}
+
+	pub fn check_invariants(&self) -> Result<(), String> {
+		for (idx, cell) in self.cells.iter().enumerate() {
+			if let Cell::Box { part } = *cell {
+				let start = idx.checked_sub(part);
+				let whole = start.is_some_and(|start| (0..SCALE)
+					.all(|p| self.cells.get(start + p) == Some(&Cell::Box { part: p })));
+				if !whole {
+					let (line, column) = self.location(idx);
+					return Err(format!("incomplete box at line {line}, column {column}"));
+				}
+			}
+		}
+
+		let walls = self.cells
+			.iter()
+			.filter(|cell| **cell == Cell::Wall)
+			.count();
+		if walls != self.walls {
+			return Err(format!("{} walls have been overwritten", self.walls - walls));
+		}
+
+		if self.cells[self.robot] != Cell::Empty {
+			let (line, column) = self.location(self.robot);
+			return Err(format!("robot is not on an empty cell at line {line}, column {column}"));
+		}
+
+		Ok(())
+	}
+}
+
+impl<const SCALE: usize> InstructionProcessor for Warehouse<SCALE> {
+	fn move_left(&mut self) {
+		self.relative_move(-1);
+	}
+
+	fn move_right(&mut self) {
+		self.relative_move(1);
+	}
+
+	fn move_up(&mut self) {
+		self.relative_move(-(self.width as isize));
+	}
+
+	fn move_down(&mut self) {
+		self.relative_move(self.width as isize);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::board::InstructionReader;
+    use super::*;
+
+	#[test]
+	fn test_move_left() {
+		let input = b"#..O.@.O..#";
+		//          b"##....[]..@...[]....##\n";
+		let mut board: Part2Board = input.as_slice().into();
+
+		for &instruction in b"<<<<<" {
+			board.process_instruction(instruction);
+		}
+		
+		let result = board.to_string();
+		assert_eq!(result, "##.[]@........[]....##\n");
+	}
+
+
+	#[test]
+	fn test_move_right() {
+		let input = b"#..O.@.O..#";
+		//          b"##....[]..@...[]....##\n"
+		let mut board: Part2Board = input.as_slice().into();
+
+		for &instruction in b">>>>>" {
+			board.process_instruction(instruction);
+		}
+		
+		let result = board.to_string();
+		assert_eq!(result, "##....[].......@[]..##\n");
+	}
+
+	#[test]
+	fn test_move_up() {
+		let input = b"\
+########
+#.O....#
+#......#
+#.O....#
+#.@....#
+########
+";
+		let mut board: Part2Board = input.as_slice().into();
+
+		for instruction in b">>^<v<^^" {
+			board.process_instruction(*instruction);
+		}
+
+		assert_eq!(board.robot, 58);
+	}
+
+	#[test]
+	fn test_part1_example() {
+		let input = b"\
+########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########";
+		let mut board: Part1Board = input.as_slice().into();
+
+		for &instruction in b"<^^>>>vv<v>>v<<" {
+			board.process_instruction(instruction);
+		}
+
+		assert_eq!(board.to_string(), "\
+########
+#....OO#
+##.....#
+#.....O#
+#.#O@..#
+#...O..#
+#...O..#
+########
+");
+		assert_eq!(board.sum_gps(), 2028);
+	}
+
+	#[test]
+	fn test_part2_example() {
+		let input = b"\
+#######
+#...#.#
+#.....#
+#..OO@#
+#..O..#
+#.....#
+#######";
+		let mut board: Part2Board = input.as_slice().into();
+
+		for &instruction in b"<vv<<^^<<^^" {
+			board.process_instruction(instruction);
+		}
+
+		assert_eq!(board.to_string(), "\
+##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############
+");
+		assert_eq!(board.sum_gps(), 105 + 207 + 306);
+	}
+
+	#[test]
+	fn test_invariants() {
+		let input = b"\
+######
+#.O@.#
+######";
+		let mut board: Part2Board = input.as_slice().into();
+		assert_eq!(board.check_invariants(), Ok(()));
+
+		board.cells[19] = Cell::Empty;
+		assert_eq!(board.check_invariants(), Err("incomplete box at line 2, column 3".to_string()));
+
+		let mut board: Part2Board = input.as_slice().into();
+		board.cells[0] = Cell::Box { part: 0 };
+		board.cells[1] = Cell::Box { part: 1 };
+		assert_eq!(board.check_invariants(), Err("2 walls have been overwritten".to_string()));
+
+		let mut board: Part2Board = input.as_slice().into();
+		board.robot = 18;
+		assert_eq!(board.check_invariants(), Err("robot is not on an empty cell at line 2, column 3".to_string()));
+	}
+
+	#[test]
+	fn test_wide_boxes() {
+		let input = b"\
+#######
+#.....#
+#.O...#
+#..@..#
+#######";
+		let mut board: Warehouse<3> = input.as_slice().into();
+
+		// push the three-cell box left until it hits the wall, then push it
+		// up from beneath its right edge
+		for &instruction in b"^<<<<v<^" {
+			board.process_instruction(instruction);
+		}
+
+		assert_eq!(board.to_string(), "\
+#####################
+###[=]............###
+###..@............###
+###...............###
+#####################
+");
+	}
+}
@@ -1,11 +1,13 @@
 use utils::timer::Timer;
-use std::collections::{HashSet,VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap,HashSet};
 use std::fs::read;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-type Score = u32;
+type Score = u64;
 
-#[derive(Eq,PartialEq,Copy,Clone,Hash)]
+#[derive(Eq,PartialEq,Ord,PartialOrd,Copy,Clone,Hash,Debug)]
 enum Direction {
     Up,
     Down,
@@ -14,6 +16,24 @@ enum Direction {
 }
 
 impl Direction {
+    const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    const fn turns(&self) -> [Self; 2] {
+        match self {
+            Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+            Direction::Left | Direction::Right => [Direction::Up, Direction::Down]
+        }
+    }
+
+    const fn offset(&self, width: usize) -> isize {
+        match self {
+            Direction::Up => -(width as isize),
+            Direction::Down => width as isize,
+            Direction::Left => -1,
+            Direction::Right => 1
+        }
+    }
+
     const fn opposite(&self) -> Self {
         match self {
             Direction::Up => Direction::Down,
@@ -22,6 +42,28 @@ impl Direction {
             Direction::Right => Direction::Left
         }
     }
+
+    const fn arrow(&self) -> u8 {
+        match self {
+            Direction::Up => b'^',
+            Direction::Down => b'v',
+            Direction::Left => b'<',
+            Direction::Right => b'>'
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" | "north" | "^" => Ok(Direction::Up),
+            "down" | "south" | "v" => Ok(Direction::Down),
+            "left" | "west" | "<" => Ok(Direction::Left),
+            "right" | "east" | ">" => Ok(Direction::Right),
+            _ => Err(format!("Unknown direction: {s}"))
+        }
+    }
 }
 
 const MOVE_PENALTY: Score = 1;
@@ -32,6 +74,9 @@ struct Maze<'a> {
     width: usize,
     start: usize,
     end: usize,
+    move_penalty: Score,
+    turn_penalty: Score,
+    start_direction: Direction
 }
 
 impl<'a> From<&'a [u8]> for Maze<'a> {
@@ -53,158 +98,204 @@ impl<'a> From<&'a [u8]> for Maze<'a> {
             board: m,
             width,
             start,
-            end
+            end,
+            move_penalty: MOVE_PENALTY,
+            turn_penalty: TURN_PENALTY,
+            start_direction: Direction::Right
         }
     }
 }
 
+type State = (usize, Direction);
+
+struct Solution {
+    best_score: Score,
+    best_seats: HashSet<usize>,
+    scores: Vec<Score>,
+    on_best_path: Vec<bool>
+}
+
 impl Maze<'_> {
-    fn get_scores(&self) -> Vec<Score> {
-        let mut scores: Vec<Score> = vec![Score::MAX; self.board.len()];
-        let mut queue: VecDeque<(u32, usize, Direction)> = VecDeque::new();
-
-        queue.push_back((0, self.start, Direction::Right));
-        while let Some((cur_score, pos, direction)) = queue.pop_front() {
-            if cur_score < scores[pos] && cur_score < scores[self.end] {
-                scores[pos] = cur_score;
-            }
-            else {
-                continue;
-            }
-    
-            queue.extend(
-                [
-                    (Direction::Left, -1isize), 
-                    (Direction::Right, 1),
-                    (Direction::Down, self.width as isize),
-                    (Direction::Up, -(self.width as isize))
-                ]
-                .iter()
-                .filter_map(|(next_direction, offset)| {
-                    let next_pos = ((pos as isize) + offset) as usize;
-                    if self.board[next_pos] != b'#' && *next_direction != direction.opposite() {
-                        let next_score = cur_score + if *next_direction != direction {
-                            TURN_PENALTY + MOVE_PENALTY
-                        }
-                        else {
-                            MOVE_PENALTY
-                        };
-                        Some((next_score, next_pos, *next_direction))
-                    }
-                    else {
-                        None
-                    }
-                })
-            );
+    fn index(&self, (pos, direction): State) -> usize {
+        direction as usize * self.board.len() + pos
+    }
+
+    fn step(&self, pos: usize, direction: Direction) -> Option<usize> {
+        let next_pos = pos.checked_add_signed(direction.offset(self.width))?;
+        match self.board.get(next_pos) {
+            Some(b'#' | b'\n') | None => None,
+            Some(_) => Some(next_pos)
         }
+    }
 
-        scores
+    fn successors(&self, (pos, direction): State) -> impl Iterator<Item = (State, Score)> + '_ {
+        let forward = self.step(pos, direction)
+            .map(|next_pos| ((next_pos, direction), self.move_penalty));
+        let turns = direction
+            .turns()
+            .map(|d| ((pos, d), self.turn_penalty));
+
+        forward.into_iter().chain(turns)
     }
 
-    fn count_best_seats(&self) -> usize {
-        let board_len = self.board.len();
-        let mut visited = vec![Score::MAX; board_len * 4];
-        let mut queue = VecDeque::new();
+    fn predecessors(&self, (pos, direction): State) -> impl Iterator<Item = (State, Score)> + '_ {
+        let backward = self.step(pos, direction.opposite())
+            .map(|prev_pos| ((prev_pos, direction), self.move_penalty));
+        let turns = direction
+            .turns()
+            .map(|d| ((pos, d), self.turn_penalty));
 
-        // do the same as part 1, but this time we need to keep track of the direction
-        // so that we can efficiently backtrack later
-        queue.push_back((0, self.start, Direction::Right));
-        while let Some((cur_score, pos, direction)) = queue.pop_front() {
-            let x = &mut visited[direction as usize * board_len + pos];
-            if cur_score < *x {
-                *x = cur_score;
-            }
-            else {
+        backward.into_iter().chain(turns)
+    }
+
+    fn solve(&self) -> Option<Solution> {
+        let mut scores = vec![Score::MAX; self.board.len() * 4];
+        let mut heap = BinaryHeap::new();
+
+        let start = (self.start, self.start_direction);
+        scores[self.index(start)] = 0;
+        heap.push(Reverse((0, start)));
+
+        while let Some(Reverse((cur_score, state))) = heap.pop() {
+            if cur_score > scores[self.index(state)] {
                 continue;
             }
 
-            if cur_score > visited[direction as usize * board_len + self.end] || pos == self.end {
+            for (next, cost) in self.successors(state) {
+                let next_score = cur_score + cost;
+                let x = &mut scores[self.index(next)];
+                if next_score < *x {
+                    *x = next_score;
+                    heap.push(Reverse((next_score, next)));
+                }
+            }
+        }
+
+        let best_score = Direction::ALL
+            .iter()
+            .map(|&d| scores[self.index((self.end, d))])
+            .min()
+            .filter(|&score| score != Score::MAX)?;
+
+        // walk backwards from every optimal end state, following only the
+        // moves and turns whose cost accounts exactly for the score difference
+        let mut on_best_path = vec![false; scores.len()];
+        let mut stack: Vec<State> = Direction::ALL
+            .iter()
+            .map(|&d| (self.end, d))
+            .filter(|&state| scores[self.index(state)] == best_score)
+            .collect();
+
+        while let Some(state) = stack.pop() {
+            let i = self.index(state);
+            if on_best_path[i] {
                 continue;
             }
+            on_best_path[i] = true;
 
-            queue.extend(
-                [
-                    (Direction::Left, -1isize), 
-                    (Direction::Right, 1),
-                    (Direction::Down, self.width as isize),
-                    (Direction::Up, -(self.width as isize))
-                ]
-                .iter()
-                .filter_map(|(next_direction, offset)| {
-                    let next_pos = ((pos as isize) + offset) as usize;
-                    if self.board[next_pos] != b'#' && *next_direction != direction.opposite() {
-                        if *next_direction != direction {
-                            Some((cur_score + TURN_PENALTY, pos, *next_direction))
-                        }
-                        else {
-                            Some((cur_score + MOVE_PENALTY, next_pos, *next_direction))
-                        }
-                    }
-                    else {
-                        None
-                    }
+            stack.extend(self
+                .predecessors(state)
+                .filter(|&(prev, cost)| {
+                    let prev_score = scores[self.index(prev)];
+                    prev_score != Score::MAX && prev_score + cost == scores[i]
                 })
+                .map(|(prev, _)| prev)
             );
         }
 
-        // find the best score at the end
-        let best_score = (0..4)
-            .map(|i| visited[i * board_len + self.end])
-            .min()
-            .unwrap();
-
-        // for each of the best scores at the end, add to the queue to start
-        // backtracking
-        for i in 0..4 {
-            if visited[i * board_len + self.end] == best_score {
-                queue.push_back((visited[i * board_len + self.end], self.end, match i {
-                    0 => Direction::Up,
-                    1 => Direction::Down,
-                    2 => Direction::Left,
-                    3 => Direction::Right,
-                    _ => panic!("Shouldn't happen")
-                }));
+        let best_seats = on_best_path
+            .iter()
+            .enumerate()
+            .filter(|(_, &on_path)| on_path)
+            .map(|(i, _)| i % self.board.len())
+            .collect();
+
+        Some(Solution {
+            best_score,
+            best_seats,
+            scores,
+            on_best_path
+        })
+    }
+
+    // turning in place gives consecutive states at the same position
+    fn optimal_routes(&self, solution: &Solution) -> Vec<Vec<State>> {
+        let mut routes = Vec::new();
+        let mut route = vec![(self.start, self.start_direction)];
+        self.extend_routes(solution, &mut route, &mut routes);
+        routes
+    }
+
+    fn optimal_route(&self, solution: &Solution) -> Vec<State> {
+        let mut route = vec![(self.start, self.start_direction)];
+        let mut state = route[0];
+        while state.0 != self.end {
+            let score = solution.scores[self.index(state)];
+            state = self
+                .successors(state)
+                .find(|&(next, cost)| {
+                    let i = self.index(next);
+                    solution.on_best_path[i] && solution.scores[i] == score + cost
+                })
+                .map(|(next, _)| next)
+                .expect("Every state on an optimal path should lead on to the end");
+            route.push(state);
+        }
+
+        route
+    }
+
+    fn extend_routes(&self, solution: &Solution, route: &mut Vec<State>, routes: &mut Vec<Vec<State>>) {
+        let state = *route.last().unwrap();
+        let score = solution.scores[self.index(state)];
+
+        if state.0 == self.end {
+            routes.push(route.clone());
+            return;
+        }
+
+        for (next, cost) in self.successors(state) {
+            let i = self.index(next);
+            if solution.on_best_path[i] && solution.scores[i] == score + cost {
+                route.push(next);
+                self.extend_routes(solution, route, routes);
+                route.pop();
             }
         }
+    }
 
-        // backtrack from the end taking only the paths that match the move or
-        // turn penalty for the next position
-        let mut best_seats = HashSet::new();
-        while let Some((cur_score, pos, direction)) = queue.pop_front() {
-            best_seats.insert(pos);
-            
-            if pos == self.start {
-                break;
+    // each tile shows the direction the reindeer leaves it in
+    fn render_route(&self, route: &[State]) -> String {
+        let mut output = self.board.to_vec();
+        for w in route.windows(2) {
+            let (pos, direction) = w[0];
+            if pos != w[1].0 && pos != self.start {
+                output[pos] = direction.arrow();
             }
+        }
 
-            queue.extend(
-                [
-                    (Direction::Right, -1isize), 
-                    (Direction::Left, 1),
-                    (Direction::Up, self.width as isize),
-                    (Direction::Down, -(self.width as isize))
-                ]
-                .iter()
-                .filter_map(|(next_direction, offset)| {
-                    let next_pos = ((pos as isize) + offset) as usize;
-                    if self.board[next_pos] != b'#' && *next_direction != direction.opposite() {
-                        if *next_direction == direction && visited[*next_direction as usize * board_len + next_pos] == cur_score - MOVE_PENALTY {
-                            return Some((cur_score - MOVE_PENALTY, next_pos, *next_direction));
-                        }
-                        else if *next_direction != direction && visited[*next_direction as usize * board_len + pos] == cur_score - TURN_PENALTY {
-                            return Some((cur_score - TURN_PENALTY, pos, *next_direction));
-                        }
-                    }
-                    
-                    None
-                })
-            );
+        String::from_utf8(output).expect("Maze should be ASCII")
+    }
+
+    fn render_seats(&self, best_seats: &HashSet<usize>) -> String {
+        let mut output = self.board.to_vec();
+        for &pos in best_seats {
+            output[pos] = b'O';
         }
 
-        best_seats.len()
+        String::from_utf8(output).expect("Maze should be ASCII")
     }
 }
 
+// costs are capped at u32 so that no route's score can overflow
+fn parse_penalty(arg: Option<String>, name: &str) -> Score {
+    arg
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|&penalty| penalty > 0)
+        .map(Score::from)
+        .unwrap_or_else(|| panic!("{name} should be a positive integer no larger than {}", u32::MAX))
+}
+
 fn main() {
     let _timer = Timer::new();
     
@@ -216,17 +307,249 @@ fn main() {
     let input = read(path)
         .expect("Should be able to read from input");
 
-    let maze: Maze = input
+    let mut maze: Maze = input
         .as_slice()
         .into();
 
+    let mut print_routes = false;
+    let mut render = false;
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--move-cost" => maze.move_penalty = parse_penalty(args.next(), "Move cost"),
+            "--turn-cost" => maze.turn_penalty = parse_penalty(args.next(), "Turn cost"),
+            "--facing" => maze.start_direction = args
+                .next()
+                .expect("Should have a direction after --facing")
+                .parse()
+                .expect("Direction should be one of up, down, left or right"),
+            "--routes" => print_routes = true,
+            "--render" => render = true,
+            _ => panic!("Unknown option: {arg}")
+        }
+    }
+
+    let solution = maze
+        .solve()
+        .expect("The end should be reachable");
+
+    if print_routes {
+        for (n, route) in maze.optimal_routes(&solution).iter().enumerate() {
+            let tiles: Vec<String> = route
+                .iter()
+                .enumerate()
+                .filter(|&(i, (pos, _))| i == 0 || route[i - 1].0 != *pos)
+                .map(|(_, (pos, _))| format!("({},{})", pos % maze.width, pos / maze.width))
+                .collect();
+            println!("Route {}: {}", n + 1, tiles.join(" "));
+        }
+    }
+
+    if render {
+        if cfg!(feature = "part2") {
+            print!("{}", maze.render_seats(&solution.best_seats));
+        }
+        else {
+            print!("{}", maze.render_route(&maze.optimal_route(&solution)));
+        }
+    }
+
     let answer = if cfg!(feature = "part2") {
-        maze.count_best_seats()
+        solution.best_seats.len()
     }
     else {
-        let scores = maze.get_scores();
-        scores[maze.end] as usize
+        solution.best_score as usize
     };
 
     println!("Answer: {answer}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_1: &[u8] = b"\
+###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############
+";
+
+    const EXAMPLE_2: &[u8] = b"\
+#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#.#
+#.#.#.#...#...#.#
+#.#.#.#.###.#.#.#
+#...#.#.#.....#.#
+#.#.#.#.#.#####.#
+#.#...#.#.#.....#
+#.#.#####.#.###.#
+#.#.#.......#...#
+#.#.###.#####.###
+#.#.#...#.....#.#
+#.#.#.#####.###.#
+#.#.#.........#.#
+#.#.#.#########.#
+#S#.............#
+#################
+";
+
+    fn route_score(route: &[State]) -> Score {
+        route
+            .windows(2)
+            .map(|w| if w[0].0 == w[1].0 { TURN_PENALTY } else { MOVE_PENALTY })
+            .sum()
+    }
+
+    #[test]
+    fn test_example_1() {
+        let maze = Maze::from(EXAMPLE_1);
+        let solution = maze.solve().unwrap();
+        assert_eq!(solution.best_score, 7036);
+        assert_eq!(solution.best_seats.len(), 45);
+    }
+
+    #[test]
+    fn test_example_2() {
+        let maze = Maze::from(EXAMPLE_2);
+        let solution = maze.solve().unwrap();
+        assert_eq!(solution.best_score, 11048);
+        assert_eq!(solution.best_seats.len(), 64);
+    }
+
+    #[test]
+    fn test_optimal_routes() {
+        for example in [EXAMPLE_1, EXAMPLE_2] {
+            let maze = Maze::from(example);
+            let solution = maze.solve().unwrap();
+            let routes = maze.optimal_routes(&solution);
+            assert!(routes.contains(&maze.optimal_route(&solution)));
+
+            let mut seats = HashSet::new();
+            for route in &routes {
+                assert_eq!(route_score(route), solution.best_score);
+                seats.extend(route.iter().map(|(pos, _)| *pos));
+            }
+            assert_eq!(seats, solution.best_seats);
+        }
+    }
+
+    #[test]
+    fn test_arrival_direction() {
+        // the junction below the end is first reached facing left (2007), but
+        // arriving later facing up (3005) avoids a turn, so scores must be
+        // tracked per direction rather than per position
+        let maze = Maze::from(b"\
+######
+#E####
+#....#
+#.##.#
+#....#
+###S.#
+######
+".as_slice());
+        let solution = maze.solve().unwrap();
+        assert_eq!(solution.best_score, 3006);
+        assert_eq!(maze.optimal_routes(&solution).len(), 1);
+    }
+
+    #[test]
+    fn test_render_seats() {
+        let maze = Maze::from(EXAMPLE_1);
+        let solution = maze.solve().unwrap();
+        assert_eq!(maze.render_seats(&solution.best_seats), "\
+###############
+#.......#....O#
+#.#.###.#.###O#
+#.....#.#...#O#
+#.###.#####.#O#
+#.#.#.......#O#
+#.#.#####.###O#
+#..OOOOOOOOO#O#
+###O#O#####O#O#
+#OOO#O....#O#O#
+#O#O#O###.#O#O#
+#OOOOO#...#O#O#
+#O###.#.#.#O#O#
+#O..#.....#OOO#
+###############
+");
+    }
+
+    #[test]
+    fn test_render_route() {
+        let maze = Maze::from(EXAMPLE_2);
+        let solution = maze.solve().unwrap();
+        let rendered: Vec<String> = maze
+            .optimal_routes(&solution)
+            .iter()
+            .map(|route| maze.render_route(route))
+            .collect();
+        assert!(rendered.contains(&"\
+#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#^#
+#.#.#.#...#...#^#
+#.#.#.#.###.#.#^#
+#>>v#.#.#.....#^#
+#^#v#.#.#.#####^#
+#^#v..#.#.#>>>>^#
+#^#v#####.#^###.#
+#^#v#..>>>>^#...#
+#^#v###^#####.###
+#^#v#>>^#.....#.#
+#^#v#^#####.###.#
+#^#v#^........#.#
+#^#v#^#########.#
+#S#>>^..........#
+#################
+".to_string()));
+    }
+
+    #[test]
+    fn test_custom_penalties() {
+        let mut maze = Maze::from(b"\
+######
+#E####
+#....#
+#.##.#
+#....#
+###S.#
+######
+".as_slice());
+        maze.move_penalty = 2;
+        maze.turn_penalty = 1;
+        let solution = maze.solve().unwrap();
+        // six moves and three turns, up and around the left-hand side
+        assert_eq!(solution.best_score, 15);
+
+        // three turns either way round
+        maze.move_penalty = 1;
+        maze.turn_penalty = u32::MAX.into();
+        let solution = maze.solve().unwrap();
+        assert_eq!(solution.best_score, 3 * u32::MAX as Score + 6);
+
+        let mut maze = Maze::from(EXAMPLE_1);
+        maze.start_direction = Direction::Up;
+        let solution = maze.solve().unwrap();
+        assert_eq!(solution.best_score, 7036 - 1000);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let maze = Maze::from(b"#####\n#S#E#\n#####\n".as_slice());
+        assert!(maze.solve().is_none());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{MachineError, OpCode, STEP_LIMIT};
+
+#[derive(Clone, Copy)]
+enum Combo {
+    Literal(u64),
+    A,
+    B,
+    C
+}
+
+impl Combo {
+    fn decode(operand: u64, ip: usize) -> Result<Self, MachineError> {
+        match operand {
+            0..=3 => Ok(Self::Literal(operand)),
+            4 => Ok(Self::A),
+            5 => Ok(Self::B),
+            6 => Ok(Self::C),
+            _ => Err(MachineError::ReservedOperand { ip })
+        }
+    }
+
+    #[inline]
+    fn value(self, registers: &[u64; 3]) -> u64 {
+        match self {
+            Self::Literal(v) => v,
+            Self::A => registers[0],
+            Self::B => registers[1],
+            Self::C => registers[2]
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Instruction {
+    Adv(Combo),
+    Bxl(u64),
+    Bst(Combo),
+    Jnz(usize),
+    Bxc,
+    Out(Combo),
+    Bdv(Combo),
+    Cdv(Combo),
+    // only raised if execution reaches it
+    Fault(MachineError)
+}
+
+pub struct CompiledProgram {
+    // one per address, since jumps may land on odd addresses
+    instructions: Vec<Instruction>
+}
+
+impl CompiledProgram {
+    pub fn new(pipeline: &[u64]) -> Self {
+        let instructions = (0..pipeline.len())
+            .map(|ip| Self::decode(pipeline, ip).unwrap_or_else(Instruction::Fault))
+            .collect();
+
+        Self {
+            instructions
+        }
+    }
+
+    fn decode(pipeline: &[u64], ip: usize) -> Result<Instruction, MachineError> {
+        let op_code = OpCode::try_from(pipeline[ip])
+            .map_err(|value| MachineError::InvalidOpCode { ip, value })?;
+        let operand = pipeline
+            .get(ip + 1)
+            .copied()
+            .ok_or(MachineError::TruncatedInstruction { ip })?;
+
+        Ok(match op_code {
+            OpCode::Adv => Instruction::Adv(Combo::decode(operand, ip)?),
+            OpCode::Bxl => Instruction::Bxl(operand),
+            OpCode::Bst => Instruction::Bst(Combo::decode(operand, ip)?),
+            OpCode::Jnz => Instruction::Jnz(operand as usize),
+            OpCode::Bxc => Instruction::Bxc,
+            OpCode::Out => Instruction::Out(Combo::decode(operand, ip)?),
+            OpCode::Bdv => Instruction::Bdv(Combo::decode(operand, ip)?),
+            OpCode::Cdv => Instruction::Cdv(Combo::decode(operand, ip)?)
+        })
+    }
+
+    #[inline]
+    fn shift_a(registers: &[u64; 3], combo: Combo, ip: usize) -> Result<u64, MachineError> {
+        let shift = combo.value(registers);
+        u32::try_from(shift)
+            .ok()
+            .and_then(|s| registers[0].checked_shr(s))
+            .ok_or(MachineError::ShiftOverflow { ip, shift })
+    }
+
+    // stops early if `emit` returns false, otherwise halts like Machine::run
+    pub fn execute_with(&self, mut registers: [u64; 3], mut emit: impl FnMut(u64) -> bool) -> Result<[u64; 3], MachineError> {
+        let mut ip = 0;
+        let mut steps = 0;
+
+        while let Some(&instruction) = self.instructions.get(ip) {
+            if steps == STEP_LIMIT {
+                return Err(MachineError::StepLimitExceeded { limit: STEP_LIMIT });
+            }
+            steps += 1;
+
+            match instruction {
+                Instruction::Adv(combo) => {
+                    registers[0] = Self::shift_a(&registers, combo, ip)?;
+                },
+                Instruction::Bxl(operand) => {
+                    registers[1] ^= operand;
+                },
+                Instruction::Bst(combo) => {
+                    registers[1] = combo.value(&registers) % 8;
+                },
+                Instruction::Jnz(target) => {
+                    if registers[0] != 0 {
+                        ip = target;
+                        continue;
+                    }
+                },
+                Instruction::Bxc => {
+                    registers[1] ^= registers[2];
+                },
+                Instruction::Out(combo) => {
+                    if !emit(combo.value(&registers) % 8) {
+                        return Ok(registers);
+                    }
+                },
+                Instruction::Bdv(combo) => {
+                    registers[1] = Self::shift_a(&registers, combo, ip)?;
+                },
+                Instruction::Cdv(combo) => {
+                    registers[2] = Self::shift_a(&registers, combo, ip)?;
+                },
+                Instruction::Fault(e) => return Err(e)
+            }
+
+            ip += 2;
+        }
+
+        Ok(registers)
+    }
+
+    pub fn matches(&self, registers: [u64; 3], expected: &[u64]) -> bool {
+        let mut count = 0;
+        let mut diverged = false;
+
+        let result = self.execute_with(registers, |value| {
+            diverged = expected.get(count) != Some(&value);
+            count += 1;
+            !diverged
+        });
+
+        result.is_ok() && !diverged && count == expected.len()
+    }
+
+    pub fn find_matches<'a>(&'a self, a_values: impl IntoIterator<Item = u64> + 'a, b: u64, c: u64, expected: &'a [u64]) -> impl Iterator<Item = u64> + 'a {
+        a_values
+            .into_iter()
+            .filter(move |&a| self.matches([a, b, c], expected))
+    }
+}
@@ -4,6 +4,10 @@ use std::fs::read_to_string;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+mod compiled;
+
+use compiled::CompiledProgram;
+
 enum OpCode {
     Adv,
     Bxl,
@@ -15,18 +19,43 @@ enum OpCode {
     Cdv
 }
 
-impl From<u64> for OpCode {
-    fn from(value: u64) -> Self {
+impl TryFrom<u64> for OpCode {
+    type Error = u64;
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
         match value {
-            0 => Self::Adv,
-            1 => Self::Bxl,
-            2 => Self::Bst,
-            3 => Self::Jnz,
-            4 => Self::Bxc,
-            5 => Self::Out,
-            6 => Self::Bdv,
-            7 => Self::Cdv,
-            _ => panic!("Invalid OpCode")
+            0 => Ok(Self::Adv),
+            1 => Ok(Self::Bxl),
+            2 => Ok(Self::Bst),
+            3 => Ok(Self::Jnz),
+            4 => Ok(Self::Bxc),
+            5 => Ok(Self::Out),
+            6 => Ok(Self::Bdv),
+            7 => Ok(Self::Cdv),
+            _ => Err(value)
+        }
+    }
+}
+
+// so that programs which never halt can be detected
+const STEP_LIMIT: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MachineError {
+    InvalidOpCode { ip: usize, value: u64 },
+    ReservedOperand { ip: usize },
+    ShiftOverflow { ip: usize, shift: u64 },
+    StepLimitExceeded { limit: usize },
+    TruncatedInstruction { ip: usize }
+}
+
+impl std::fmt::Display for MachineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOpCode { ip, value } => write!(f, "Invalid opcode {value} at {ip}"),
+            Self::ReservedOperand { ip } => write!(f, "Reserved combo operand 7 at {ip}"),
+            Self::ShiftOverflow { ip, shift } => write!(f, "Shift by {shift} overflows at {ip}"),
+            Self::StepLimitExceeded { limit } => write!(f, "Program did not halt within {limit} steps"),
+            Self::TruncatedInstruction { ip } => write!(f, "Instruction at {ip} has no operand")
         }
     }
 }
@@ -50,7 +79,7 @@ impl FromStr for Machine {
     type Err = MachineParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let input: Vec<&str> = s
-            .split(|c: char| matches!(c, ':' | '\n' | ','))
+            .split([':', '\n', ','])
             .collect();
 
         let registers: [u64; 3] = [
@@ -76,75 +105,99 @@ impl FromStr for Machine {
 }
 
 impl Machine {
-    fn get_combo_operand(&self) -> u64 {
-        let val = self.pipeline[self.ip + 1];
+    fn get_literal_operand(&self) -> Result<u64, MachineError> {
+        self.pipeline
+            .get(self.ip + 1)
+            .copied()
+            .ok_or(MachineError::TruncatedInstruction { ip: self.ip })
+    }
+
+    fn get_combo_operand(&self) -> Result<u64, MachineError> {
+        let val = self.get_literal_operand()?;
         match val {
-            0..=3 => val,
-            4 => self.registers[0],
-            5 => self.registers[1],
-            6 => self.registers[2],
-            _ => panic!("Invalid operand")
+            0..=3 => Ok(val),
+            4 => Ok(self.registers[0]),
+            5 => Ok(self.registers[1]),
+            6 => Ok(self.registers[2]),
+            _ => Err(MachineError::ReservedOperand { ip: self.ip })
         }
     }
 
-    fn run(&mut self) -> Vec<u64> {
+    fn shift_a(&self) -> Result<u64, MachineError> {
+        let shift = self.get_combo_operand()?;
+        u32::try_from(shift)
+            .ok()
+            .and_then(|s| self.registers[0].checked_shr(s))
+            .ok_or(MachineError::ShiftOverflow { ip: self.ip, shift })
+    }
+
+    fn run(&mut self) -> Result<Vec<u64>, MachineError> {
+        self.run_with_limit(STEP_LIMIT)
+    }
+
+    fn run_with_limit(&mut self, limit: usize) -> Result<Vec<u64>, MachineError> {
         let mut result = Vec::new();
+        let mut steps = 0;
 
         while self.ip < self.pipeline.len() {
-            let op_code: OpCode = self.pipeline[self.ip].into();
+            if steps == limit {
+                return Err(MachineError::StepLimitExceeded { limit });
+            }
+            steps += 1;
+
+            let op_code = OpCode::try_from(self.pipeline[self.ip])
+                .map_err(|value| MachineError::InvalidOpCode { ip: self.ip, value })?;
 
             match op_code {
                 OpCode::Adv => {
-                    self.registers[0] >>= self.get_combo_operand();
+                    self.registers[0] = self.shift_a()?;
                 },
                 OpCode::Bxl => {
-                    self.registers[1] ^= self.pipeline[self.ip+1];
+                    self.registers[1] ^= self.get_literal_operand()?;
                 },
                 OpCode::Bst => {
-                    self.registers[1] = self.get_combo_operand() % 8;
+                    self.registers[1] = self.get_combo_operand()? % 8;
                 },
                 OpCode::Jnz => {
+                    let target = self.get_literal_operand()?;
                     if self.registers[0] != 0 {
-                        self.ip = self.pipeline[self.ip+1] as usize;
+                        self.ip = target as usize;
                         continue;
                     }
                 },
                 OpCode::Bxc => {
+                    self.get_literal_operand()?;
                     self.registers[1] ^= self.registers[2];
                 },
                 OpCode::Out => {
-                    result.push(self.get_combo_operand() % 8);
+                    result.push(self.get_combo_operand()? % 8);
                 },
                 OpCode::Bdv => {
-                    self.registers[1] = self.registers[0] >> self.get_combo_operand();
+                    self.registers[1] = self.shift_a()?;
                 },
                 OpCode::Cdv => {
-                    self.registers[2] = self.registers[0] >> self.get_combo_operand();
+                    self.registers[2] = self.shift_a()?;
                 }
             }
 
             self.ip += 2;
         }
 
-        result
+        Ok(result)
     }
 }
 
-fn get_quine(a: u64, m: &mut Machine, i: usize, possibilities: &mut BTreeSet<u64>) -> Option<u64> {
-    for b in 0..8 {
-        let a = a << 3 | b;
-        m.registers = [ a, 0, 0 ];
-        m.ip = 0;
-        
-        if m.run() == m.pipeline[i..] {
-            if i == 0 {
-                possibilities.insert(a);
-            }
-            else {
-                let answer = get_quine(a, m, i-1, possibilities);
-                if answer.is_some() {
-                    return answer;
-                }
+fn get_quine(a: u64, program: &CompiledProgram, pipeline: &[u64], i: usize, possibilities: &mut BTreeSet<u64>) -> Option<u64> {
+    let candidates = (0..8).map(|b| a << 3 | b);
+
+    for a in program.find_matches(candidates, 0, 0, &pipeline[i..]) {
+        if i == 0 {
+            possibilities.insert(a);
+        }
+        else {
+            let answer = get_quine(a, program, pipeline, i-1, possibilities);
+            if answer.is_some() {
+                return answer;
             }
         }
     }
@@ -166,20 +219,24 @@ fn main() {
 
     if cfg!(feature = "part2") {
         let mut possibilities = BTreeSet::new();
+        let program = CompiledProgram::new(&machine.pipeline);
         let i = machine.pipeline.len() - 1;
-        let result = get_quine(0, &mut machine, i, &mut possibilities)
+        let result = get_quine(0, &program, &machine.pipeline, i, &mut possibilities)
             .expect("There should be a solution");
         println!("Answer: {result}");
     }
     else {
-        let result = machine.run();
-        println!("Answer: {:?}", result);
+        match machine.run() {
+            Ok(result) => println!("Answer: {:?}", result),
+            Err(e) => eprintln!("Machine halted: {e}")
+        }
     };
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::rng::xorshift;
 
     #[test]
     fn test_parse() {
@@ -196,8 +253,112 @@ mod tests {
             ip: 0
         };
 
-        machine.run();
+        machine.run().expect("Program should halt");
 
         assert_eq!(machine.registers[1], 1, "Register B should be 1");
     }
+
+    fn machine_with(registers: [u64; 3], pipeline: Vec<u64>) -> Machine {
+        Machine {
+            registers,
+            pipeline,
+            ip: 0
+        }
+    }
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut machine = machine_with([0, 0, 0], vec![8, 0]);
+        assert_eq!(machine.run(), Err(MachineError::InvalidOpCode { ip: 0, value: 8 }));
+    }
+
+    #[test]
+    fn test_reserved_operand() {
+        let mut machine = machine_with([0, 0, 0], vec![1, 7, 5, 7]);
+        assert_eq!(machine.run(), Err(MachineError::ReservedOperand { ip: 2 }));
+    }
+
+    #[test]
+    fn test_shift_overflow() {
+        let mut machine = machine_with([1, 64, 0], vec![0, 5]);
+        assert_eq!(machine.run(), Err(MachineError::ShiftOverflow { ip: 0, shift: 64 }));
+
+        let mut machine = machine_with([1, 63, 0], vec![0, 5]);
+        assert_eq!(machine.run(), Ok(vec![]));
+        assert_eq!(machine.registers[0], 0);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut machine = machine_with([1, 0, 0], vec![3, 0]);
+        assert_eq!(machine.run_with_limit(100), Err(MachineError::StepLimitExceeded { limit: 100 }));
+    }
+
+    #[test]
+    fn test_truncated_instruction() {
+        let mut machine = machine_with([0, 0, 0], vec![5, 0, 5]);
+        assert_eq!(machine.run(), Err(MachineError::TruncatedInstruction { ip: 2 }));
+
+        // jumping to an odd address leaves the final opcode without an operand
+        let mut machine = machine_with([1, 0, 0], vec![3, 3, 0, 1]);
+        assert_eq!(machine.run(), Err(MachineError::TruncatedInstruction { ip: 3 }));
+    }
+
+    #[test]
+    fn test_random_pipelines() {
+        let mut state: u64 = 0x2024_1217_dead_beef;
+        let mut next = || xorshift(&mut state);
+
+        for _ in 0..10_000 {
+            let len = (next() % 16) as usize;
+            let pipeline = (0..len).map(|_| next() % 10).collect();
+            let registers = [next(), next() % 128, next() % 128];
+            let mut machine = machine_with(registers, pipeline);
+
+            if let Ok(output) = machine.run_with_limit(1_000) {
+                assert!(output.iter().all(|&v| v < 8), "Output values should be 3-bit");
+                assert!(machine.ip >= machine.pipeline.len(), "Normal halt should leave ip past the end");
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_matches_interpreter() {
+        let mut state: u64 = 0x1217_2024_c0ff_ee00;
+        let mut next = || xorshift(&mut state);
+
+        for _ in 0..1_000 {
+            let len = (next() % 16) as usize;
+            let pipeline: Vec<u64> = (0..len).map(|_| next() % 10).collect();
+            let registers = [next(), next() % 128, next() % 128];
+            let program = CompiledProgram::new(&pipeline);
+            let mut machine = machine_with(registers, pipeline);
+
+            let mut output = Vec::new();
+            let compiled = program.execute_with(registers, |v| { output.push(v); true });
+            let interpreted = machine.run();
+
+            match interpreted {
+                Ok(expected) => {
+                    assert_eq!(compiled, Ok(machine.registers));
+                    assert_eq!(output, expected);
+                },
+                Err(e) => assert_eq!(compiled, Err(e))
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_batch() {
+        let pipeline = vec![0, 3, 5, 4, 3, 0];
+        let program = CompiledProgram::new(&pipeline);
+
+        let found: Vec<u64> = program
+            .find_matches(0..200_000, 0, 0, &pipeline)
+            .collect();
+        assert_eq!(found, (117440..117448).collect::<Vec<u64>>());
+
+        let mut possibilities = BTreeSet::new();
+        assert_eq!(get_quine(0, &program, &pipeline, pipeline.len() - 1, &mut possibilities), Some(117440));
+    }
 }
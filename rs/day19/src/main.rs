@@ -1,22 +1,112 @@
 use utils::timer::Timer;
-use std::collections::HashSet;
 use std::fs::read_to_string;
 use std::path::PathBuf;
 
-fn count_possible_designs<'a>(design: &'a [u8], patterns: &HashSet<&[u8]>, max_length: usize) -> u64 {
-    let mut dp = vec![0u64; design.len()+1];
-    dp[0] = 1; // base case
+mod trie;
 
-    for idx in 1..dp.len() {
-        for end in idx..(idx+max_length).min(dp.len()) {
-            let subdesign = &design[idx-1..end];
-            if patterns.contains(subdesign) {
-                dp[end] += dp[end - subdesign.len()];
-            }
+use trie::Trie;
+
+// every pattern that fits at a reachable position offers `extend` to the
+// position where it ends; an offer of None is left out, and a merge of None
+// gives up
+fn arrange<T: Clone>(
+    design: &[u8],
+    trie: &Trie,
+    empty: T,
+    extend: impl Fn(&T, usize, usize) -> Option<T>,
+    merge: impl Fn(T, T) -> Option<T>
+) -> Option<Vec<Option<T>>> {
+    let mut dp: Vec<Option<T>> = vec![None; design.len()+1];
+    dp[0] = Some(empty); // base case
+
+    for idx in 0..design.len() {
+        let Some(current) = dp[idx].clone() else {
+            continue;
+        };
+
+        for (len, pattern) in trie.prefixes(&design[idx..]) {
+            let Some(offer) = extend(&current, idx, pattern) else {
+                continue;
+            };
+            dp[idx + len] = Some(match dp[idx + len].take() {
+                Some(existing) => merge(existing, offer)?,
+                None => offer
+            });
         }
     }
 
-    *dp.last().unwrap()
+    Some(dp)
+}
+
+fn count_possible_designs(design: &[u8], trie: &Trie) -> Option<u128> {
+    let dp = arrange(design, trie, 1u128, |&ways, _, _| Some(ways), u128::checked_add)?;
+
+    Some(dp.last().copied().flatten().unwrap_or(0))
+}
+
+#[derive(Clone, Copy)]
+struct Step {
+    cost: u64,
+    from: Option<(usize, usize)>
+}
+
+// None if the design is impossible or no arrangement's cost fits in a u64
+fn cheapest_arrangement(design: &[u8], trie: &Trie, cost: impl Fn(usize) -> u64) -> Option<(u64, Vec<usize>)> {
+    let dp = arrange(
+        design,
+        trie,
+        Step { cost: 0, from: None },
+        |step, idx, pattern| Some(Step {
+            cost: step.cost.checked_add(cost(pattern))?,
+            from: Some((idx, pattern))
+        }),
+        |a, b| Some(if b.cost < a.cost { b } else { a })
+    )?;
+
+    let total = dp.last().copied().flatten()?.cost;
+
+    let mut arrangement = Vec::new();
+    let mut idx = design.len();
+    while let Some((start, pattern)) = dp[idx].and_then(|step| step.from) {
+        arrangement.push(pattern);
+        idx = start;
+    }
+
+    arrangement.reverse();
+    Some((total, arrangement))
+}
+
+fn fewest_towels(design: &[u8], trie: &Trie) -> Option<(u64, Vec<usize>)> {
+    cheapest_arrangement(design, trie, |_| 1)
+}
+
+// patterns may be given a cost, as in `bwu=3`, and cost 1 otherwise
+fn parse_input(data: &str) -> (Vec<&str>, Vec<u64>, Vec<&str>) {
+    let (patterns, designs) = data
+        .split_once("\n\n")
+        .or_else(|| data.split_once("\r\n\r\n"))
+        .expect("Input should have blank line");
+
+    let (patterns, costs) = patterns
+        .split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| match x.split_once('=') {
+            Some((pattern, cost)) => (pattern.trim(), cost
+                .trim()
+                .parse::<u64>()
+                .expect("Towel cost should be a number")),
+            None => (x, 1)
+        })
+        .unzip();
+
+    let designs = designs
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .collect();
+
+    (patterns, costs, designs)
 }
 
 fn main() {
@@ -30,33 +120,50 @@ fn main() {
     let data = read_to_string(path)
         .expect("Should be able to read from");
 
-    let (patterns, designs) = data
-        .split_once("\n\n")
-        .expect("Input should have blank line");
+    let (patterns, costs, designs) = parse_input(&data);
+    let trie = Trie::new(patterns.iter().map(|x| x.as_bytes()));
 
-    let patterns: HashSet<&[u8]> = patterns
-        .split(", ")
-        .map(|x| x.as_bytes())
-        .collect();
+    if std::env::args().skip(2).any(|arg| arg == "--explain") {
+        let show = |arrangement: &[usize]| arrangement
+            .iter()
+            .map(|&p| patterns[p])
+            .collect::<Vec<&str>>()
+            .join(" + ");
+
+        let mut impossible = Vec::new();
+        for design in &designs {
+            let fewest = fewest_towels(design.as_bytes(), &trie);
+            let cheapest = cheapest_arrangement(design.as_bytes(), &trie, |p| costs[p]);
+            match (fewest, cheapest) {
+                (Some((count, fewest)), Some((cost, cheapest))) => {
+                    println!("{design}: fewest {count} ({}), cheapest {cost} ({})", show(&fewest), show(&cheapest));
+                },
+                // the design can be made, but every way costs more than a u64
+                (Some((count, fewest)), None) => {
+                    println!("{design}: fewest {count} ({}), cheapest costs too much to count", show(&fewest));
+                },
+                (None, _) => impossible.push(*design)
+            }
+        }
+
+        println!("Impossible designs:");
+        for design in impossible {
+            println!("{design}");
+        }
+    }
 
-    let max_length = patterns
-        .iter()
-        .map(|x| x.len())
-        .max()
-        .unwrap();
-    
     let answer = if cfg!(feature = "part2") {
         designs
-            .lines()
-            .map(|x| count_possible_designs(x.as_bytes(), &patterns, max_length))
-            .sum()
+            .iter()
+            .map(|x| count_possible_designs(x.as_bytes(), &trie))
+            .try_fold(0u128, |total, count| total.checked_add(count?))
+            .expect("Number of arrangements should fit in a u128")
     }
     else {
         designs
-            .lines()
-            .map(|x| count_possible_designs(x.as_bytes(), &patterns, max_length) > 0)
-            .filter(|x| *x)
-            .count() as u64
+            .iter()
+            .filter(|x| fewest_towels(x.as_bytes(), &trie).is_some())
+            .count() as u128
     };
     
     println!("Answer: {answer}");
@@ -64,34 +171,113 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-     use super::*;
+    use super::*;
+
+    const EXAMPLE: &str = "\
+r, wr, b, g, bwu, rb, gb, br
+
+brwrr
+bggr
+gbbr
+rrbgbr
+ubwu
+bwurrg
+brgr
+bbrwb
+";
 
     #[test]
     fn test_simple_case() {
-        let mut patterns: HashSet<&[u8]> = HashSet::new();
-        patterns.insert(b"br");
-        patterns.insert(b"g");
-        patterns.insert(b"gr");
+        let trie = Trie::new([b"br".as_slice(), b"g", b"gr"]);
 
-        assert!(count_possible_designs(b"brgr", &patterns, 2) > 0);
+        assert!(count_possible_designs(b"brgr", &trie).unwrap() > 0);
     }
 
     #[test]
     fn test_simple_case_ng() {
-        let mut patterns: HashSet<&[u8]> = HashSet::new();
-        patterns.insert(b"br");
-        patterns.insert(b"g");
-        patterns.insert(b"gr");
+        let trie = Trie::new([b"br".as_slice(), b"g", b"gr"]);
 
-        assert_eq!(count_possible_designs(b"brugr", &patterns, 2), 0);
+        assert_eq!(count_possible_designs(b"brugr", &trie), Some(0));
     }
 
     #[test]
     fn test_simple_case_ng_2() {
-        let mut patterns: HashSet<&[u8]> = HashSet::new();
-        patterns.insert(b"rgb");
-        patterns.insert(b"bwu");
+        let trie = Trie::new([b"rgb".as_slice(), b"bwu"]);
+
+        assert_eq!(count_possible_designs(b"rgbwu", &trie), Some(0));
+    }
+
+    #[test]
+    fn test_example() {
+        let (patterns, _, designs) = parse_input(EXAMPLE);
+        let trie = Trie::new(patterns.iter().map(|x| x.as_bytes()));
+
+        let counts: Vec<u128> = designs
+            .iter()
+            .map(|x| count_possible_designs(x.as_bytes(), &trie).unwrap())
+            .collect();
+        assert_eq!(counts, vec![2, 1, 4, 6, 0, 1, 2, 0]);
+
+        for (design, count) in designs.iter().zip(counts) {
+            let arrangement = fewest_towels(design.as_bytes(), &trie);
+            assert_eq!(arrangement.is_some(), count > 0);
+            if let Some((_, arrangement)) = arrangement {
+                let rebuilt: String = arrangement
+                    .iter()
+                    .map(|&p| patterns[p])
+                    .collect();
+                assert_eq!(&rebuilt, design);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fewest_towels() {
+        let (patterns, _, designs) = parse_input(EXAMPLE);
+        let trie = Trie::new(patterns.iter().map(|x| x.as_bytes()));
+
+        let fewest: Vec<Option<u64>> = designs
+            .iter()
+            .map(|x| fewest_towels(x.as_bytes(), &trie).map(|(count, _)| count))
+            .collect();
+        assert_eq!(fewest, vec![Some(3), Some(4), Some(2), Some(4), None, Some(4), Some(3), None]);
+    }
+
+    #[test]
+    fn test_cheapest_arrangement() {
+        let (patterns, costs, designs) = parse_input("r=1, wr=10, b, g, bwu, rb, gb, br=10\n\nbrwrr\n");
+        assert_eq!(costs, vec![1, 10, 1, 1, 1, 1, 1, 10]);
+
+        let trie = Trie::new(patterns.iter().map(|x| x.as_bytes()));
+        let (cost, arrangement) = cheapest_arrangement(designs[0].as_bytes(), &trie, |p| costs[p]).unwrap();
+        let towels: Vec<&str> = arrangement
+            .iter()
+            .map(|&p| patterns[p])
+            .collect();
+
+        assert_eq!(cost, 13);
+        assert_eq!(towels, vec!["b", "r", "wr", "r"]);
+    }
+
+    #[test]
+    fn test_cost_overflow() {
+        // possible, but too expensive to cost
+        let trie = Trie::new([b"a".as_slice()]);
+        assert_eq!(fewest_towels(b"aa", &trie), Some((2, vec![0, 0])));
+        assert_eq!(cheapest_arrangement(b"aa", &trie, |_| u64::MAX), None);
+
+        // one way overflows, but there's a cheaper one that doesn't
+        let (patterns, costs, designs) = parse_input("a=18446744073709551615, aa=1\n\naa\n");
+        let trie = Trie::new(patterns.iter().map(|x| x.as_bytes()));
+        assert_eq!(cheapest_arrangement(designs[0].as_bytes(), &trie, |p| costs[p]), Some((1, vec![1])));
+    }
+
+    #[test]
+    fn test_large_counts() {
+        let trie = Trie::new([b"a".as_slice(), b"aa"]);
 
-        assert_eq!(count_possible_designs(b"rgbwu", &patterns, 3), 0);
+        // fibonacci(101) no longer fits in a u64
+        assert_eq!(count_possible_designs(&[b'a'; 100], &trie), Some(573147844013817084101));
+        assert_eq!(count_possible_designs(&[b'a'; 200], &trie), None);
     }
 }
@@ -0,0 +1,56 @@
+pub struct Trie {
+    nodes: Vec<Node>
+}
+
+#[derive(Default)]
+struct Node {
+    children: Vec<(u8, usize)>,
+    pattern: Option<usize>
+}
+
+impl Trie {
+    // if a pattern is repeated, the first one is kept
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut nodes = vec![Node::default()];
+
+        for (idx, pattern) in patterns.into_iter().enumerate() {
+            let mut node = 0;
+            for &b in pattern {
+                node = match nodes[node].children.iter().find(|(ch, _)| *ch == b) {
+                    Some(&(_, child)) => child,
+                    None => {
+                        nodes.push(Node::default());
+                        let child = nodes.len() - 1;
+                        nodes[node].children.push((b, child));
+                        child
+                    }
+                };
+            }
+            nodes[node].pattern.get_or_insert(idx);
+        }
+
+        Self {
+            nodes
+        }
+    }
+
+    fn child(&self, node: usize, b: u8) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .find(|(ch, _)| *ch == b)
+            .map(|&(_, child)| child)
+    }
+
+    // (length, pattern index), shortest first
+    pub fn prefixes<'a>(&'a self, text: &'a [u8]) -> impl Iterator<Item = (usize, usize)> + 'a {
+        text
+            .iter()
+            .scan(0, move |node, &b| {
+                *node = self.child(*node, b)?;
+                Some(*node)
+            })
+            .enumerate()
+            .filter_map(move |(i, node)| self.nodes[node].pattern.map(|p| (i + 1, p)))
+    }
+}
@@ -13,18 +13,18 @@ struct RaceTrack<'a> {
 impl<'a> From<&'a [u8]> for RaceTrack<'a> {
     fn from(board: &'a [u8]) -> Self {
         let width = board
-            .into_iter()
+            .iter()
             .position(|ch| *ch == b'\n')
             .map(|w| w + 1)
             .unwrap_or_else(|| board.len());
 
         let start = board
-            .into_iter()
+            .iter()
             .position(|ch| *ch == b'S')
             .expect("There should be a start");
 
         let end = board
-            .into_iter()
+            .iter()
             .position(|ch| *ch == b'E')
             .expect("There should be an end");
 
@@ -22,8 +22,8 @@ fn count_antinodes(board: &[u8], antennae: &HashMap<u8, Vec<usize>>) -> usize {
         .unwrap_or_else(|| board.len());
 
     let antinodes: HashSet<usize> = HashSet::from_iter(antennae
-        .iter()
-        .flat_map(|(_ch, points)| {
+        .values()
+        .flat_map(|points| {
             points[0..points.len()-1]
                 .iter()
                 .enumerate()
@@ -62,8 +62,8 @@ fn count_extended_antinodes(board: &[u8], antennae: &HashMap<u8, Vec<usize>>) ->
         .unwrap_or_else(|| board.len());
 
     let antinodes: HashSet<usize> = HashSet::from_iter(antennae
-        .iter()
-        .flat_map(|(_ch, points)| {
+        .values()
+        .flat_map(|points| {
             points[0..points.len()-1]
                 .iter()
                 .enumerate()
@@ -1,130 +1,209 @@
 use utils::timer::Timer;
-use std::collections::{BTreeMap, BTreeSet};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fs::read;
 use std::path::PathBuf;
 
-const EMPTY: u16 = u16::MAX;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Span {
+    id: usize,
+    offset: usize,
+    len: usize
+}
 
-fn expand_disk(compacted_disk: &[u8]) -> Vec<u16> {
-    compacted_disk
-        .iter()
-        .enumerate()
-        .filter(|(_, ch)| ch.is_ascii_digit())
-        .flat_map(|(idx, ch)| {
-            (0..(ch - b'0'))
-                .map(move |_| {
-                    if idx % 2 == 1 { 
-                        EMPTY
-                    }
-                    else {
-                        (idx / 2) as u16
-                    }
-                })
-        })
-        .collect::<Vec<u16>>()
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Gap {
+    offset: usize,
+    len: usize
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Move {
+    id: usize,
+    from: usize,
+    to: usize,
+    len: usize
 }
 
-fn frag(disk: &mut [u16]) {
-    let mut i = 0;
-    let mut j = disk.len() - 1;
+// empty gaps are left out, and anything that isn't a digit is ignored
+fn parse_disk_map(compacted_disk: &[u8]) -> (Vec<Span>, Vec<Gap>) {
+    let mut files = Vec::new();
+    let mut gaps: Vec<Gap> = Vec::new();
+    let mut offset = 0;
 
-    loop {
-        while i < disk.len() && disk[i] != EMPTY {
-            i += 1;
+    for (idx, ch) in compacted_disk.iter().filter(|ch| ch.is_ascii_digit()).enumerate() {
+        let len = (ch - b'0') as usize;
+        if idx % 2 == 1 {
+            // an empty file between two gaps joins them into one
+            match gaps.last_mut() {
+                Some(last) if last.offset + last.len == offset => last.len += len,
+                _ if len > 0 => gaps.push(Gap { offset, len }),
+                _ => {}
+            }
         }
-        while i < j && disk[j] == EMPTY {
-            j -= 1;
+        else {
+            files.push(Span { id: idx / 2, offset, len });
         }
+        offset += len;
+    }
+
+    (files, gaps)
+}
 
-        disk.swap(i, j);
-        i += 1;
-        
-        if j == 0 || i >= j {
+fn frag(files: &[Span], gaps: &[Gap], mut on_move: impl FnMut(Move)) -> Vec<Span> {
+    let mut result = Vec::with_capacity(files.len() + gaps.len());
+    let mut remaining: Vec<Span> = files.to_vec();
+    let mut gaps = gaps.iter().copied().peekable();
+
+    // files are never moved right, so once the next gap is beyond the last
+    // file there is nothing left to do
+    while let (Some(last), Some(gap)) = (remaining.last_mut(), gaps.peek_mut()) {
+        if gap.offset >= last.offset {
             break;
         }
-        
-        j -= 1;
+
+        let moved = last.len.min(gap.len);
+        if moved > 0 {
+            result.push(Span { id: last.id, offset: gap.offset, len: moved });
+        }
+
+        for k in 0..moved {
+            on_move(Move { id: last.id, from: last.offset + last.len - 1 - k, to: gap.offset + k, len: 1 });
+        }
+
+        last.len -= moved;
+        gap.offset += moved;
+        gap.len -= moved;
+
+        if last.len == 0 {
+            remaining.pop();
+        }
+        if gap.len == 0 {
+            gaps.next();
+        }
     }
+
+    result.extend(remaining);
+    result
 }
 
-fn get_disk_map(compacted_disk: &[u8]) -> (Vec<usize>, BTreeMap<usize, BTreeSet<usize>>) {
-    let (file_lengths, free_space, _) = compacted_disk
+fn defrag(files: &[Span], gaps: &[Gap], mut on_move: impl FnMut(Move)) -> Vec<Span> {
+    // files are at most 9 blocks long, so keep a min-heap of gaps by offset
+    // for each length, with anything longer in the last one
+    const LONGEST: usize = 9;
+
+    let mut by_length: Vec<BinaryHeap<Reverse<(usize, usize)>>> = vec![BinaryHeap::new(); LONGEST + 1];
+    for gap in gaps {
+        by_length[gap.len.min(LONGEST)].push(Reverse((gap.offset, gap.len)));
+    }
+
+    let mut result: Vec<Span> = files.to_vec();
+    for file in result.iter_mut().rev() {
+        let Some((bucket, offset, len)) = (file.len.min(LONGEST)..=LONGEST)
+            .filter_map(|bucket| by_length[bucket]
+                .peek()
+                .map(|Reverse((offset, len))| (bucket, *offset, *len)))
+            .filter(|(_, offset, len)| *offset < file.offset && *len >= file.len)
+            .min_by_key(|(_, offset, _)| *offset) else {
+            continue;
+        };
+
+        by_length[bucket].pop();
+        on_move(Move { id: file.id, from: file.offset, to: offset, len: file.len });
+        file.offset = offset;
+
+        // the space the file leaves behind is to the right of every file
+        // still to be moved, so it can never be used
+        let left = len - file.len;
+        if left > 0 {
+            by_length[left.min(LONGEST)].push(Reverse((offset + file.len, left)));
+        }
+    }
+
+    result
+}
+
+// a span's positions form an arithmetic series
+fn checksum(spans: &[Span]) -> u128 {
+    spans
         .iter()
-        .enumerate()
-        .filter(|(_, ch)| ch.is_ascii_digit())
-        .map(|(idx, &ch)| (idx, (ch - b'0') as usize))
-        .fold((Vec::new(), BTreeMap::new(), 0usize), |(mut file_lengths, mut free_space, acc), (idx, len)| {
-            if idx % 2 == 1 {
-                free_space.entry(len).or_insert(BTreeSet::new()).insert(acc);
-            }
-            else {
-                file_lengths.push(len);
-            }
-            (file_lengths, free_space, acc + len)
-        });
-    (file_lengths, free_space)
+        .map(|span| {
+            let (offset, len) = (span.offset as u128, span.len as u128);
+            let positions = offset * len + len * len.saturating_sub(1) / 2;
+            span.id as u128 * positions
+        })
+        .sum()
 }
 
-fn defrag(disk: &mut [u16], file_lengths: &[usize], mut free_spaces: BTreeMap<usize, BTreeSet<usize>>) {
-    let mut j = disk.len() - 1;
+const GLYPHS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
-    'outer: loop {
-        while disk[j] == EMPTY {
-            j -= 1;
-            if j == 0 {
-                break 'outer;
-            }
-        }
+fn glyph(id: usize) -> char {
+    GLYPHS[id % GLYPHS.len()] as char
+}
 
-        let file_id = disk[j];
-        let file_length = file_lengths[file_id as usize];
+// a 256-colour palette entry to tell apart files sharing a glyph; the first
+// 62 files have none, so small disks draw just as the puzzle does
+fn colour(id: usize) -> Option<usize> {
+    let round = id / GLYPHS.len();
+    (round > 0).then(|| 16 + (round - 1) % 216)
+}
 
-        let (space_length, space_index) = {
-            let Some((space_length, space_index)) = free_spaces
-                .range(file_length..)
-                .map(|(space_size, spaces)| (space_size, spaces.first()))
-                .filter(|(_, index)| index.is_some_and(|&f| f < j))
-                .map(|(space_length, index)| (space_length, index.unwrap()))
-                .min_by_key(|(_, index)| **index) else {
-                if j < file_length {
-                    break 'outer;
-                }
-                j -= file_length;
-                continue;
-            };
+struct Layout {
+    blocks: Vec<Option<usize>>
+}
 
-            (*space_length, *space_index)
-        };
+impl Layout {
+    fn new(files: &[Span], size: usize) -> Self {
+        let mut blocks = vec![None; size];
+        for file in files {
+            blocks[file.offset..file.offset + file.len].fill(Some(file.id));
+        }
 
-        if space_index > j {
-            if j < file_length {
-                break 'outer;
-            }
-            j -= file_length;
-            continue;
+        Self {
+            blocks
         }
-                
-        for i in space_index..space_index+file_length {
-            disk.swap(i, j);
-            j -= 1;
+    }
+
+    fn apply(&mut self, m: Move) {
+        for k in 0..m.len {
+            self.blocks[m.from + k] = None;
+            self.blocks[m.to + k] = Some(m.id);
         }
-        
-        // bookkeeping
-        let space_left = space_length - file_length;
-        free_spaces.entry(space_length).and_modify(|indices| { indices.remove(&space_index); });
-        if space_left > 0 {
-            free_spaces.entry(space_left).or_default().insert(space_index + file_length);
+    }
+
+    fn render(&self) -> String {
+        let mut rendered = String::new();
+        for block in &self.blocks {
+            match *block {
+                None => rendered.push('.'),
+                Some(id) => match colour(id) {
+                    Some(colour) => rendered.push_str(&format!("\x1b[38;5;{colour}m{}\x1b[0m", glyph(id))),
+                    None => rendered.push(glyph(id))
+                }
+            }
         }
+
+        rendered
     }
 }
 
-fn checksum(disk: &[u16]) -> usize {
-    disk
-        .iter()
-        .enumerate()
-        .filter(|(_, &d)| d != EMPTY)
-        .map(|(idx, &d)| idx * d as usize)
-        .sum()
+fn steps(compacted_disk: &[u8], whole_files: bool, mut on_step: impl FnMut(String)) {
+    let (files, gaps) = parse_disk_map(compacted_disk);
+    let size = files.iter().map(|f| f.len).sum::<usize>() + gaps.iter().map(|g| g.len).sum::<usize>();
+
+    let mut layout = Layout::new(&files, size);
+    on_step(layout.render());
+    let on_move = |m| {
+        layout.apply(m);
+        on_step(layout.render());
+    };
+
+    if whole_files {
+        defrag(&files, &gaps, on_move);
+    }
+    else {
+        frag(&files, &gaps, on_move);
+    }
 }
 
 fn main() {
@@ -138,17 +217,20 @@ fn main() {
     let disk = read(path)
         .expect("Should be able to read from path");
 
-    let mut expanded = expand_disk(&disk);
-    
-    if cfg!(feature = "part2") {
-        let (file_lengths, free_space) = get_disk_map(&disk);
-        defrag(&mut expanded, &file_lengths, free_space);
+    if std::env::args().skip(2).any(|arg| arg == "--steps") {
+        steps(&disk, cfg!(feature = "part2"), |step| println!("{step}"));
     }
-    else {
-        frag(&mut expanded);
+
+    let (files, gaps) = parse_disk_map(&disk);
+
+    let compacted = if cfg!(feature = "part2") {
+        defrag(&files, &gaps, |_| {})
     }
+    else {
+        frag(&files, &gaps, |_| {})
+    };
 
-    let answer = checksum(&expanded);
+    let answer = checksum(&compacted);
 
     println!("Answer: {answer}");
 }
@@ -156,23 +238,184 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::rng::xorshift;
+
+    const EXAMPLE: &[u8] = b"2333133121414131402";
+
+    fn blocks(spans: &[Span]) -> Vec<Option<usize>> {
+        let end = spans.iter().map(|s| s.offset + s.len).max().unwrap_or(0);
+        let mut disk = vec![None; end];
+        for span in spans {
+            for block in &mut disk[span.offset..span.offset + span.len] {
+                assert_eq!(*block, None, "Spans should not overlap");
+                *block = Some(span.id);
+            }
+        }
+        disk
+    }
+
+    // compacts the disk the slow way, block by block
+    fn reference(compacted_disk: &[u8], whole_files: bool) -> u128 {
+        let (files, _) = parse_disk_map(compacted_disk);
+        let mut disk = blocks(&files);
+        disk.resize(compacted_disk.iter().map(|ch| (ch - b'0') as usize).sum(), None);
+
+        if !whole_files {
+            let (mut i, mut j) = (0, disk.len());
+            while i < j {
+                if disk[i].is_some() {
+                    i += 1;
+                }
+                else if disk[j - 1].is_none() {
+                    j -= 1;
+                }
+                else {
+                    disk.swap(i, j - 1);
+                }
+            }
+        }
+        else {
+            for file in files.iter().rev().filter(|f| f.len > 0) {
+                let start = disk.iter().position(|b| *b == Some(file.id)).unwrap();
+                let target = (0..start).find(|&i| disk[i..i + file.len].iter().all(Option::is_none));
+                if let Some(target) = target {
+                    for k in 0..file.len {
+                        disk.swap(target + k, start + k);
+                    }
+                }
+            }
+        }
+
+        disk
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, id)| id.map(|id| (idx * id) as u128))
+            .sum()
+    }
 
     #[test]
-    fn test_expand() {
-        let example = b"12345";
-        let expanded = expand_disk(example);
+    fn test_parse() {
+        let (files, gaps) = parse_disk_map(b"12345\n");
 
-        assert_eq!(expanded,
-            vec![0, EMPTY, EMPTY, 1, 1, 1, EMPTY, EMPTY, EMPTY, EMPTY, 2, 2, 2, 2, 2]);
+        assert_eq!(blocks(&files), vec![Some(0), None, None, Some(1), Some(1), Some(1), None, None, None, None, Some(2), Some(2), Some(2), Some(2), Some(2)]);
+        assert_eq!(gaps, vec![Gap { offset: 1, len: 2 }, Gap { offset: 6, len: 4 }]);
+    }
+
+    #[test]
+    fn test_frag() {
+        let (files, gaps) = parse_disk_map(EXAMPLE);
+        let compacted = frag(&files, &gaps, |_| {});
+
+        let expected: Vec<Option<usize>> = b"0099811188827773336446555566"
+            .iter()
+            .map(|ch| Some((ch - b'0') as usize))
+            .collect();
+        assert_eq!(blocks(&compacted), expected);
+        assert_eq!(checksum(&compacted), 1928);
     }
 
     #[test]
     fn test_defrag() {
-        let example = b"2333133121414131402";
-        let mut expanded = expand_disk(example);
-        let (file_lengths, free_space) = get_disk_map(example);
-        defrag(&mut expanded, &file_lengths, free_space);
-        let checksum = checksum(&expanded);
-        assert_eq!(checksum, 2858);
+        let (files, gaps) = parse_disk_map(EXAMPLE);
+        let compacted = defrag(&files, &gaps, |_| {});
+        assert_eq!(checksum(&compacted), 2858);
+    }
+
+    fn collect_steps(compacted_disk: &[u8], whole_files: bool) -> Vec<String> {
+        let mut rendered = Vec::new();
+        steps(compacted_disk, whole_files, |step| rendered.push(step));
+        rendered
+    }
+
+    #[test]
+    fn test_frag_steps() {
+        assert_eq!(collect_steps(b"12345", false), vec![
+            "0..111....22222",
+            "02.111....2222.",
+            "022111....222..",
+            "0221112...22...",
+            "02211122..2....",
+            "022111222......"
+        ]);
+
+        assert_eq!(collect_steps(EXAMPLE, false), vec![
+            "00...111...2...333.44.5555.6666.777.888899",
+            "009..111...2...333.44.5555.6666.777.88889.",
+            "0099.111...2...333.44.5555.6666.777.8888..",
+            "00998111...2...333.44.5555.6666.777.888...",
+            "009981118..2...333.44.5555.6666.777.88....",
+            "0099811188.2...333.44.5555.6666.777.8.....",
+            "009981118882...333.44.5555.6666.777.......",
+            "0099811188827..333.44.5555.6666.77........",
+            "00998111888277.333.44.5555.6666.7.........",
+            "009981118882777333.44.5555.6666...........",
+            "009981118882777333644.5555.666............",
+            "00998111888277733364465555.66.............",
+            "0099811188827773336446555566.............."
+        ]);
+    }
+
+    #[test]
+    fn test_defrag_steps() {
+        assert_eq!(collect_steps(EXAMPLE, true), vec![
+            "00...111...2...333.44.5555.6666.777.888899",
+            "0099.111...2...333.44.5555.6666.777.8888..",
+            "0099.1117772...333.44.5555.6666.....8888..",
+            "0099.111777244.333....5555.6666.....8888..",
+            "00992111777.44.333....5555.6666.....8888.."
+        ]);
+    }
+
+    #[test]
+    fn test_glyph() {
+        assert_eq!(glyph(0), '0');
+        assert_eq!(glyph(9), '9');
+        assert_eq!(glyph(10), 'a');
+        assert_eq!(glyph(36), 'A');
+        assert_eq!(glyph(61), 'Z');
+        assert_eq!(glyph(62), '0');
+        assert_eq!(glyph(63), '1');
+        assert_eq!(colour(61), None);
+        assert_eq!(colour(62), Some(16));
+        assert_eq!(colour(124), Some(17));
+        assert_ne!(colour(62), colour(62 + GLYPHS.len()));
+        assert_eq!(Layout::new(&[Span { id: 63, offset: 0, len: 1 }], 2).render(), "\x1b[38;5;16m1\x1b[0m.");
+    }
+
+    #[test]
+    fn test_against_blocks() {
+        let mut state: u64 = 0x0909_0909;
+        for _ in 0..300 {
+            let len = 1 + (state % 40) as usize;
+            let map: Vec<u8> = (0..len)
+                .map(|_| b'0' + (xorshift(&mut state) % 10) as u8)
+                .collect();
+
+            let (files, gaps) = parse_disk_map(&map);
+            assert_eq!(checksum(&frag(&files, &gaps, |_| {})), reference(&map, false), "{}", String::from_utf8_lossy(&map));
+            assert_eq!(checksum(&defrag(&files, &gaps, |_| {})), reference(&map, true), "{}", String::from_utf8_lossy(&map));
+        }
+    }
+
+    #[test]
+    fn test_large_disk() {
+        // far more than 65,535 files, and a checksum too big for a u64
+        let map: Vec<u8> = (0..4_000_000)
+            .map(|i| b'1' + (i * 7 % 9) as u8)
+            .collect();
+        let (files, gaps) = parse_disk_map(&map);
+        assert_eq!(files.len(), 2_000_000);
+
+        let fragged = frag(&files, &gaps, |_| {});
+        let defragged = defrag(&files, &gaps, |_| {});
+
+        let total: usize = files.iter().map(|f| f.len).sum();
+        assert_eq!(fragged.iter().map(|s| s.len).sum::<usize>(), total);
+        assert_eq!(defragged.iter().map(|s| s.len).sum::<usize>(), total);
+        assert!(checksum(&fragged) > u64::MAX as u128);
+
+        // after fragmenting, the files fill the start of the disk
+        let end = fragged.iter().map(|s| s.offset + s.len).max().unwrap();
+        assert_eq!(end, total);
     }
 }
@@ -0,0 +1,7 @@
+// xorshift64, so that randomised tests stay deterministic without pulling in a crate
+pub fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}